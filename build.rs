@@ -11,7 +11,11 @@ fn main() -> anyhow::Result<()> {
             .compile()?;
     }
 
-    slint_build::compile("ui/appwindow.slint")?;
+    // Translations of the labels in lang/<language>/LC_MESSAGES/Thermostat.po, picked by the system language or the language option.
+    let config = slint_build::CompilerConfiguration::new()
+        .with_bundled_translations("lang")
+        .with_default_translation_context(slint_build::DefaultTranslationContext::None);
+    slint_build::compile_with_config("ui/appwindow.slint", config)?;
     
     Ok(())
 }
@@ -0,0 +1,85 @@
+use std::path::Path;
+use anyhow::Result;
+use log::{error, info};
+
+const APP_NAME: &str = "Thermostat";
+
+/// Registers the app to start on login, or unregisters it, matching the `auto_start` option.
+/// Failures are only logged, e.g. when the user is not permitted to change the login items.
+pub fn apply(enabled: bool) {
+    let exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(err) => {
+            error!("Could not determine the executable to start on login: {:?}", err);
+            return;
+        },
+    };
+
+    let result = if enabled { register(&exe) } else { unregister() };
+    match result {
+        Ok(()) if enabled => info!("Registered {:?} to start on login", exe),
+        Ok(()) => {},
+        Err(err) => error!("Could not {} start on login: {:?}", if enabled { "enable" } else { "disable" }, err),
+    }
+}
+
+#[cfg(windows)]
+const RUN_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Run";
+
+#[cfg(windows)]
+fn register(exe: &Path) -> Result<()> {
+    use winreg::{enums::HKEY_CURRENT_USER, RegKey};
+
+    let (run, _) = RegKey::predef(HKEY_CURRENT_USER).create_subkey(RUN_KEY)?;
+    run.set_value(APP_NAME, &format!("\"{}\"", exe.display()))?;
+    Ok(())
+}
+
+#[cfg(windows)]
+fn unregister() -> Result<()> {
+    use winreg::{enums::{HKEY_CURRENT_USER, KEY_SET_VALUE}, RegKey};
+
+    let run = RegKey::predef(HKEY_CURRENT_USER).open_subkey_with_flags(RUN_KEY, KEY_SET_VALUE)?;
+    match run.delete_value(APP_NAME) {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()), // Was never registered.
+        result => Ok(result?),
+    }
+}
+
+/// Path of the autostart desktop entry, see the XDG autostart spec.
+#[cfg(target_os = "linux")]
+fn desktop_entry_path() -> Result<std::path::PathBuf> {
+    let dirs = directories::BaseDirs::new().ok_or_else(|| anyhow::anyhow!("no home directory"))?;
+    Ok(dirs.config_dir().join("autostart").join("thermostat.desktop"))
+}
+
+#[cfg(target_os = "linux")]
+fn register(exe: &Path) -> Result<()> {
+    let path = desktop_entry_path()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    std::fs::write(path, format!(
+        "[Desktop Entry]\nType=Application\nName={}\nExec=\"{}\"\nX-GNOME-Autostart-enabled=true\n",
+        APP_NAME, exe.display()))?;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn unregister() -> Result<()> {
+    match std::fs::remove_file(desktop_entry_path()?) {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()), // Was never registered.
+        result => Ok(result?),
+    }
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+fn register(_exe: &Path) -> Result<()> {
+    anyhow::bail!("not supported on this platform")
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+fn unregister() -> Result<()> {
+    Ok(()) // Can never have been registered.
+}
@@ -0,0 +1,75 @@
+use std::{cell::Cell, time::{Duration, Instant}};
+use log::{info, warn};
+use slint::ComponentHandle;
+
+use crate::{countdown::{self, Countdown}, modify_config, modify_config_automatically, thermostats, toast, Api, AppWindow, Functions, Singletons, Texts, ThermostatConfig};
+
+thread_local! {
+    static BOOST: Cell<Option<Boost>> = const { Cell::new(None) };
+    static COUNTDOWN: Countdown = Countdown::new(|singletons, time_left| singletons.set_boost_countdown(time_left), countdown::minutes_seconds);
+}
+
+/// A temporary raise of the target temperature.
+#[derive(Debug, Clone, Copy)]
+struct Boost {
+    endpoint: usize,
+    boosted: f32, // Target while boosting, anything else means the user changed it since.
+    restore: f32, // Target to go back to afterwards.
+}
+
+pub fn is_active() -> bool {
+    BOOST.get().is_some()
+}
+
+/// Has the boost end at `target_temp` instead of the target from before it, e.g. because a schedule entry became active.
+pub fn set_restore_target(target_temp: f32) {
+    if let Some(boost) = BOOST.get() {
+        BOOST.set(Some(Boost { restore: target_temp, ..boost }));
+    }
+}
+
+/// Raises the target temperature by `boost_delta` for `boost_minutes`, or extends the boost in progress by as long.
+pub fn boost(ui: &AppWindow, api: &Api) {
+    let options = ui.global::<Singletons>().get_options();
+    if options.read_only {
+        return;
+    }
+
+    let duration = Duration::from_secs(options.boost_minutes as u64 * 60);
+    if is_active() {
+        COUNTDOWN.with(|countdown| countdown.extend(ui, duration));
+        info!("Extended the boost by {} minutes", options.boost_minutes);
+        toast::show_info(ui, &ui.global::<Texts>().invoke_boost_extended(options.boost_minutes));
+        return;
+    }
+
+    let restore = ui.global::<Singletons>().get_config().target_temp;
+    modify_config(ui, api, |cfg: &mut ThermostatConfig| cfg.target_temp += options.boost_delta);
+    let boosted = ui.global::<Singletons>().get_config().target_temp; // Clamped to the maximum.
+    if boosted == restore {
+        toast::show_info(ui, &ui.global::<Texts>().invoke_target_at_maximum());
+        return;
+    }
+
+    info!("Boosting the target from {} to {} for {} minutes", restore, boosted, options.boost_minutes);
+    BOOST.set(Some(Boost { endpoint: thermostats::selected(), boosted, restore }));
+
+    let api = api.clone();
+    COUNTDOWN.with(|countdown| countdown.start(ui, Instant::now() + duration, move |ui| {
+        let Some(boost) = BOOST.take() else { return true };
+        if !thermostats::is_selected(boost.endpoint) {
+            warn!("Boost ended, but another thermostat is selected since; leaving its target boosted");
+            return true;
+        }
+        if ui.global::<Singletons>().get_config().target_temp != boost.boosted {
+            info!("Boost ended, keeping the target that was set since");
+            return true;
+        }
+
+        info!("Boost ended, restoring the target to {}", boost.restore);
+        modify_config_automatically(ui, &api, |cfg: &mut ThermostatConfig| cfg.target_temp = boost.restore);
+        let texts = ui.global::<Texts>();
+        toast::show_automatic(ui, &texts.invoke_boost_ended(), &texts.invoke_target_back_to(ui.global::<Functions>().invoke_format_temp(boost.restore)));
+        true
+    }));
+}
@@ -0,0 +1,80 @@
+use std::cell::RefCell;
+use chrono::Local;
+use log::{error, info};
+use slint::{ComponentHandle, Model};
+
+use crate::{thermostats, toast, AppWindow, ConnectionStatus, Functions, Singletons, Texts};
+
+thread_local! {
+    // Only ever touched from the UI thread.
+    // Kept around, on Linux the copied text is only there for as long as the clipboard that copied it.
+    static CLIPBOARD: RefCell<Option<arboard::Clipboard>> = const { RefCell::new(None) };
+}
+
+/// Copies the state of the selected thermostat as a line of text, for pasting it into a chat or a bug report.
+pub fn copy_state(ui: &AppWindow) {
+    let text = describe_state(ui);
+    let result = CLIPBOARD.with_borrow_mut(|clipboard| {
+        let clipboard = match clipboard {
+            Some(clipboard) => clipboard,
+            None => clipboard.insert(arboard::Clipboard::new()?),
+        };
+        clipboard.set_text(&text)
+    });
+
+    match result {
+        Ok(()) => {
+            info!("Copied state to the clipboard: {}", text);
+            toast::show_info(ui, &ui.global::<Texts>().invoke_copied_state());
+        },
+        Err(err) => {
+            error!("Could not copy state to the clipboard: {:?}", err);
+            toast::show_warning(ui, &ui.global::<Texts>().invoke_could_not_copy_state());
+        },
+    }
+}
+
+/// E.g. "Bedroom at 2026-10-14 21:05:12: 20.5 °C, target 21 °C, 640 ppm CO₂, 45 % RH, heating, connected".
+fn describe_state(ui: &AppWindow) -> String {
+    let singletons = ui.global::<Singletons>();
+    let functions = ui.global::<Functions>();
+    let state = singletons.get_state();
+    let config = singletons.get_config();
+
+    let name = singletons.get_thermostat_names().row_data(thermostats::selected()).unwrap_or_else(|| "Thermostat".into());
+    let mut parts = vec![];
+    if state.available {
+        parts.push(functions.invoke_format_temp(state.current_temp).to_string());
+    } else {
+        parts.push("sensor offline".to_owned());
+    }
+    parts.push(if !config.master_switch {
+        "heating off".to_owned()
+    } else if config.force {
+        "force on".to_owned()
+    } else {
+        format!("target {}", functions.invoke_format_temp(config.target_temp))
+    });
+    if state.available {
+        parts.push(if config.require_co2 {
+            format!("{} ppm CO₂ (target {} ppm)", state.co2, config.co2_target)
+        } else {
+            format!("{} ppm CO₂", state.co2)
+        });
+        if state.has_humidity {
+            parts.push(format!("{} % RH", state.humidity.round()));
+        }
+    }
+    parts.push(if state.is_heating { "heating" } else { "not heating" }.to_owned());
+    parts.push(match singletons.get_connection() {
+        ConnectionStatus::Connected => "connected",
+        ConnectionStatus::Disconnected => "disconnected",
+        ConnectionStatus::Unauthorized => "token rejected",
+        ConnectionStatus::Rejected => "server error",
+    }.to_owned());
+    if singletons.get_stale() {
+        parts.push("cached".to_owned()); // Not received from the thermostat yet this session.
+    }
+
+    format!("{} at {}: {}", name, Local::now().format("%Y-%m-%d %H:%M:%S"), parts.join(", "))
+}
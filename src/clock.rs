@@ -0,0 +1,61 @@
+use std::{cell::RefCell, collections::{HashMap, HashSet}, sync::Mutex};
+use chrono::Utc;
+use log::{info, warn};
+use slint::{ComponentHandle, Model};
+
+use crate::{thermostats, toast, AppWindow, Singletons, Texts};
+
+/// Seconds the local clock was ahead of each server's as of its last response that said, negative if behind.
+static SKEWS: Mutex<Option<HashMap<usize, i64>>> = Mutex::new(None);
+
+thread_local! {
+    // Only ever touched from the UI thread.
+    static WARNED: RefCell<HashSet<usize>> = RefCell::new(HashSet::new()); // So the toast shows once per thermostat, not on every poll.
+}
+
+/// Compares the time a server answered at, in Unix seconds, against the local clock, if the response says.
+pub fn record(endpoint: usize, server_time: Option<i64>) {
+    if let Some(server_time) = server_time {
+        SKEWS.lock().unwrap().get_or_insert_with(HashMap::new).insert(endpoint, Utc::now().timestamp() - server_time);
+    }
+}
+
+fn skew(endpoint: usize) -> i64 {
+    SKEWS.lock().unwrap().as_ref().and_then(|skews| skews.get(&endpoint).copied()).unwrap_or_default()
+}
+
+/// Warns if the local clock is off from the server of a thermostat by more than `max_clock_skew_secs`,
+/// as the schedule and the other timed changes go by the local clock. Nothing is held back, it is only a warning.
+pub fn check(ui: &AppWindow, endpoint: usize) {
+    match describe(ui, endpoint) {
+        Some(message) if WARNED.with_borrow_mut(|warned| warned.insert(endpoint)) => {
+            warn!("{} ({} s)", message, skew(endpoint));
+            toast::show_warning(ui, &message);
+        },
+        None if WARNED.with_borrow_mut(|warned| warned.remove(&endpoint)) => info!("The clock is in sync with the server of thermostat {} again", endpoint),
+        _ => {},
+    }
+    update_display(ui);
+}
+
+/// Shows whether the clock is off from the server of the selected thermostat.
+pub fn update_display(ui: &AppWindow) {
+    let message = describe(ui, thermostats::selected()).unwrap_or_default();
+    ui.global::<Singletons>().set_clock_skew(message.into());
+}
+
+/// What is wrong with the clock, if it is off by too much.
+fn describe(ui: &AppWindow, endpoint: usize) -> Option<String> {
+    let singletons = ui.global::<Singletons>();
+    let max_skew = singletons.get_options().max_clock_skew_secs as i64;
+    let skew = skew(endpoint);
+    if max_skew == 0 || skew.abs() <= max_skew {
+        return None;
+    }
+
+    let name = singletons.get_thermostat_names().row_data(endpoint).map_or_else(|| endpoint.to_string(), String::from);
+    let mins = skew.abs() / 60;
+    let off_by = if mins < 60 { format!("{} min", mins) } else { format!("{}h {:02}m", mins / 60, mins % 60) };
+    let texts = ui.global::<Texts>();
+    Some(if skew > 0 { texts.invoke_clock_ahead(off_by.into(), name.into()) } else { texts.invoke_clock_behind(off_by.into(), name.into()) }.into())
+}
@@ -0,0 +1,89 @@
+use std::{cell::Cell, rc::Rc, time::{Duration, Instant}};
+use slint::{ComponentHandle, SharedString, Timer, TimerMode};
+
+use crate::{AppWindow, Singletons};
+
+/// Something that ends by itself after a while, like a boost, with the time left shown in the UI.
+/// Ticks once a second and runs the action passed to [`Countdown::start`] when the time is up.
+pub struct Countdown {
+    timer: Rc<Timer>,
+    until: Rc<Cell<Option<Instant>>>,
+    show: fn(&Singletons, SharedString), // Setter of the property the time left is shown in.
+    format: fn(Duration) -> String,
+}
+
+impl Countdown {
+    pub fn new(show: fn(&Singletons, SharedString), format: fn(Duration) -> String) -> Self {
+        Self { timer: Rc::new(Timer::default()), until: Rc::new(Cell::new(None)), show, format }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.until.get().is_some()
+    }
+
+    /// Counts down to `until`, replacing the countdown in progress.
+    /// Once it is up, the countdown stops and runs `expire`, unless that returns false to be tried again on the next tick.
+    pub fn start(&self, ui: &AppWindow, until: Instant, expire: impl Fn(&AppWindow) -> bool + 'static) {
+        self.until.set(Some(until));
+        self.update_display(ui);
+
+        let ui_handle = ui.as_weak();
+        let timer = Rc::downgrade(&self.timer); // The timer owns this callback.
+        let current = Rc::clone(&self.until);
+        let (show, format) = (self.show, self.format);
+        self.timer.start(TimerMode::Repeated, Duration::from_secs(1), move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let Some(until) = current.get() else { return };
+
+            if until > Instant::now() {
+                display(&ui, show, format, until);
+                return;
+            }
+            if expire(&ui) {
+                current.set(None);
+                show(&ui.global::<Singletons>(), Default::default());
+                if let Some(timer) = timer.upgrade() {
+                    timer.stop();
+                }
+            }
+        });
+    }
+
+    /// Moves the end of the countdown in progress `by` later.
+    pub fn extend(&self, ui: &AppWindow, by: Duration) {
+        if let Some(until) = self.until.get() {
+            self.until.set(Some(until + by));
+            self.update_display(ui);
+        }
+    }
+
+    pub fn stop(&self, ui: &AppWindow) {
+        self.until.set(None);
+        self.timer.stop();
+        (self.show)(&ui.global::<Singletons>(), Default::default());
+    }
+
+    fn update_display(&self, ui: &AppWindow) {
+        if let Some(until) = self.until.get() {
+            display(ui, self.show, self.format, until);
+        }
+    }
+}
+
+fn display(ui: &AppWindow, show: fn(&Singletons, SharedString), format: fn(Duration) -> String, until: Instant) {
+    // Rounded up, so it never shows nothing left while still running.
+    let remaining = Duration::from_secs(until.saturating_duration_since(Instant::now()).as_secs_f32().ceil() as u64);
+    show(&ui.global::<Singletons>(), format(remaining).into());
+}
+
+/// Time left like "4:05".
+pub fn minutes_seconds(remaining: Duration) -> String {
+    let secs = remaining.as_secs();
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+/// Time left like "1h 05m", rounded up to the minute.
+pub fn hours_minutes(remaining: Duration) -> String {
+    let secs = remaining.as_secs() + 59;
+    format!("{}h {:02}m", secs / 3600, secs % 3600 / 60)
+}
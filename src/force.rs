@@ -0,0 +1,47 @@
+use std::time::{Duration, Instant};
+use log::{info, warn};
+use slint::ComponentHandle;
+
+use crate::{countdown::{self, Countdown}, frost, modify_config_automatically, thermostats, toast, Api, AppWindow, Singletons, Texts, ThermostatConfig};
+
+thread_local! {
+    static COUNTDOWN: Countdown = Countdown::new(|singletons, time_left| singletons.set_force_countdown(time_left), countdown::minutes_seconds);
+}
+
+/// Starts the countdown to turn force on off again once it was turned on, and stops it once it is off.
+/// Turning force off and on again restarts the countdown.
+pub fn update(ui: &AppWindow, api: &Api, force: bool) {
+    let minutes = ui.global::<Singletons>().get_options().force_timeout_minutes;
+    if !force || minutes <= 0 {
+        COUNTDOWN.with(|countdown| countdown.stop(ui));
+        return;
+    }
+
+    if COUNTDOWN.with(Countdown::is_running) {
+        return; // Another setting changed while forced on, keep counting down.
+    }
+
+    info!("Force on turns off again in {} minutes", minutes);
+    let endpoint = thermostats::selected();
+    let api = api.clone();
+    let until = Instant::now() + Duration::from_secs(minutes as u64 * 60);
+    COUNTDOWN.with(|countdown| countdown.start(ui, until, move |ui| {
+        if !thermostats::is_selected(endpoint) {
+            warn!("Force on timed out, but another thermostat is selected since; leaving it on");
+            return true;
+        }
+        if !ui.global::<Singletons>().get_config().force {
+            return true; // Turned off in the meantime, e.g. by a rollback.
+        }
+        if frost::is_engaged(endpoint) {
+            info!("Force on timed out, but frost protection needs it; leaving it on");
+            return true;
+        }
+
+        info!("Force on timed out after {} minutes, turning it off", minutes);
+        modify_config_automatically(ui, &api, |cfg: &mut ThermostatConfig| cfg.force = false);
+        let texts = ui.global::<Texts>();
+        toast::show_automatic(ui, &texts.invoke_force_timeout(), &texts.invoke_force_turned_off(minutes));
+        true
+    }));
+}
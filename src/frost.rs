@@ -0,0 +1,95 @@
+use std::{cell::RefCell, collections::{HashMap, HashSet}};
+use log::{info, warn};
+use slint::ComponentHandle;
+
+use crate::{set_config_now, thermostats, toast, Api, APIResponseStateData, AppWindow, Functions, Singletons, Texts, ThermostatConfig};
+
+const HYSTERESIS: f32 = 1.0; // In °C, how far above the threshold the room has to warm up before protection lets go.
+
+thread_local! {
+    // Only ever touched from the UI thread.
+    static ENGAGED: RefCell<HashMap<usize, (bool, bool)>> = RefCell::new(HashMap::new()); // Master switch and force from before, by thermostat.
+    static READ_ONLY_WARNED: RefCell<HashSet<usize>> = RefCell::new(HashSet::new()); // Thermostats it could not engage for, warned about once.
+}
+
+/// Forces heating on while a thermostat reads below `frost_protection_temp`, whatever its config says,
+/// and puts the master switch and force back the way they were once the room has warmed up again.
+/// Read-only mode never changes the config, not even for this, so it only warns then.
+pub fn check(ui: &AppWindow, api: &Api, endpoint: usize, state: &APIResponseStateData) {
+    let options = ui.global::<Singletons>().get_options();
+    if !options.frost_protection || !state.available {
+        return;
+    }
+
+    let threshold = options.frost_protection_temp;
+    if options.read_only {
+        warn_read_only(ui, api, endpoint, state.temperature < threshold);
+        return;
+    }
+
+    let engaged = ENGAGED.with_borrow(|engaged| engaged.get(&endpoint).copied());
+    match engaged {
+        None if state.temperature < threshold => {
+            let Some(cfg) = current_config(ui, endpoint) else { return };
+            let threshold = ui.global::<Functions>().invoke_format_temp(threshold);
+            warn!("Temperature of thermostat {} dropped below {}, engaging frost protection", endpoint, threshold);
+            let texts = ui.global::<Texts>();
+            toast::show_automatic(ui, &texts.invoke_frost_protection(), &texts.invoke_frost_engaged(threshold));
+
+            ENGAGED.with_borrow_mut(|engaged| engaged.insert(endpoint, (cfg.master_switch, cfg.force)));
+            set_heating(ui, api, endpoint, true, true);
+        },
+        // Keep at it, e.g. if an update got lost or heating was turned off by hand.
+        Some(_) if state.temperature < threshold + HYSTERESIS => set_heating(ui, api, endpoint, true, true),
+        Some((master_switch, force)) => {
+            info!("Thermostat {} warmed up again, disengaging frost protection", endpoint);
+            ENGAGED.with_borrow_mut(|engaged| engaged.remove(&endpoint));
+            set_heating(ui, api, endpoint, master_switch, force);
+            let texts = ui.global::<Texts>();
+            toast::show_automatic(ui, &texts.invoke_frost_protection(), &texts.invoke_frost_disengaged());
+        },
+        None => {},
+    }
+
+    update_display(ui);
+}
+
+/// Warns once each time a thermostat drops below the threshold that frost protection cannot do anything about it.
+fn warn_read_only(ui: &AppWindow, api: &Api, endpoint: usize, below: bool) {
+    let newly_below = READ_ONLY_WARNED.with_borrow_mut(|warned| {
+        if below {
+            return warned.insert(endpoint);
+        }
+        warned.remove(&endpoint);
+        false
+    });
+    if newly_below {
+        let name = &api.endpoints[endpoint].name;
+        warn!("Temperature of thermostat {} dropped below the frost protection threshold, but the config cannot be changed in read-only mode", name);
+        toast::show_warning(ui, &ui.global::<Texts>().invoke_read_only_frost(name.into()));
+    }
+}
+
+pub fn is_engaged(endpoint: usize) -> bool {
+    ENGAGED.with_borrow(|engaged| engaged.contains_key(&endpoint))
+}
+
+/// Shows whether frost protection is engaged for the selected thermostat.
+pub fn update_display(ui: &AppWindow) {
+    ui.global::<Singletons>().set_frost_protection(is_engaged(thermostats::selected()));
+}
+
+fn current_config(ui: &AppWindow, endpoint: usize) -> Option<ThermostatConfig> {
+    if thermostats::is_selected(endpoint) {
+        Some(ui.global::<Singletons>().get_config().into())
+    } else {
+        thermostats::confirmed_config(endpoint)
+    }
+}
+
+/// Sends the switches right away, as a safety change is not something to debounce, cool down, time out or undo.
+fn set_heating(ui: &AppWindow, api: &Api, endpoint: usize, master_switch: bool, force: bool) {
+    if let Some(cfg) = current_config(ui, endpoint).filter(|cfg| cfg.master_switch != master_switch || cfg.force != force) {
+        set_config_now(ui, api, endpoint, ThermostatConfig { master_switch, force, ..cfg });
+    }
+}
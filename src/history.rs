@@ -0,0 +1,114 @@
+use std::{cell::RefCell, collections::VecDeque, fmt::Write, fs, path::Path};
+use anyhow::Result;
+use log::error;
+use slint::ComponentHandle;
+
+use crate::{write_json, AppWindow, Singletons};
+
+const CAPACITY: usize = 240; // One hour at the default poll rate
+const SAMPLE_INTERVAL_SECS: i64 = 15;
+const SPAN_SECS: i64 = 60 * 60;
+const GRAPH_HEIGHT: f32 = 100.0; // Height of the viewbox of the graph; its width is SPAN_SECS.
+const MIN_RANGE: f32 = 1.0; // Smallest temperature range in °C the graph spans, so noise does not look like a cliff.
+
+thread_local! {
+    // Only ever touched from the UI thread.
+    static HISTORY: RefCell<History> = RefCell::new(History::default());
+}
+
+/// Recent temperature readings, shown as a graph.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Default)]
+struct History {
+    samples: VecDeque<Sample>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy)]
+struct Sample {
+    time: i64, // Unix timestamp in seconds
+    temp: f32, // In °C
+}
+
+impl History {
+    fn record(&mut self, time: i64, temp: f32) {
+        // Live feed pushes and config changes also deliver states, only keep one sample per interval.
+        if self.samples.back().is_some_and(|last| time - last.time < SAMPLE_INTERVAL_SECS) {
+            return;
+        }
+
+        self.samples.push_back(Sample { time, temp });
+        self.trim(time);
+    }
+
+    /// Drops samples beyond the capacity or older than the span of the graph.
+    fn trim(&mut self, now: i64) {
+        while self.samples.len() > CAPACITY || self.samples.front().is_some_and(|s| now - s.time > SPAN_SECS) {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Lowest and highest temperature to show on the graph.
+    fn range(&self) -> Option<(f32, f32)> {
+        if self.samples.is_empty() {
+            return None;
+        }
+
+        let min = self.samples.iter().map(|s| s.temp).fold(f32::INFINITY, f32::min);
+        let max = self.samples.iter().map(|s| s.temp).fold(f32::NEG_INFINITY, f32::max);
+        let padding = ((MIN_RANGE - (max - min)) / 2.0).max(0.0);
+        Some((min - padding, max + padding))
+    }
+
+    /// Path commands of the graph in a viewbox of `SPAN_SECS` by `GRAPH_HEIGHT`.
+    fn to_commands(&self, now: i64) -> String {
+        let Some((min, max)) = self.range().filter(|_| self.samples.len() >= 2) else { return String::new() };
+
+        let mut commands = String::new();
+        for (i, sample) in self.samples.iter().enumerate() {
+            let x = (SPAN_SECS - (now - sample.time)).max(0);
+            let y = GRAPH_HEIGHT - (sample.temp - min) / (max - min) * GRAPH_HEIGHT;
+            let _ = write!(commands, "{}{} {} ", if i == 0 { "M" } else { "L" }, x, y); // Writing to a String cannot fail.
+        }
+        commands
+    }
+}
+
+/// Loads the history saved by the last session, if any.
+pub fn load(path: &Path) {
+    let Ok(contents) = fs::read_to_string(path) else { return };
+
+    match serde_json::from_str::<History>(&contents) {
+        Ok(mut history) => {
+            history.trim(chrono::Utc::now().timestamp());
+            HISTORY.set(history);
+        },
+        Err(err) => error!("Could not read temperature history from disk: {:?}", err),
+    }
+}
+
+pub fn save(path: &Path) -> Result<()> {
+    HISTORY.with_borrow(|history| write_json(history, path))
+}
+
+/// Adds a temperature reading to the history and redraws the graph.
+pub fn record(ui: &AppWindow, temp: f32) {
+    HISTORY.with_borrow_mut(|history| history.record(chrono::Utc::now().timestamp(), temp));
+    update_graph(ui);
+}
+
+/// Forgets all readings, e.g. because they are of another thermostat.
+pub fn clear(ui: &AppWindow) {
+    HISTORY.with_borrow_mut(|history| history.samples.clear());
+    update_graph(ui);
+}
+
+pub fn update_graph(ui: &AppWindow) {
+    let singletons = ui.global::<Singletons>();
+
+    HISTORY.with_borrow(|history| {
+        singletons.set_history_commands(history.to_commands(chrono::Utc::now().timestamp()).into());
+
+        let (min, max) = history.range().unwrap_or_default();
+        singletons.set_history_min(min);
+        singletons.set_history_max(max);
+    });
+}
@@ -0,0 +1,104 @@
+use std::{cell::Cell, fs, path::Path, time::{Duration, Instant}};
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use log::{error, info, warn};
+use slint::ComponentHandle;
+
+use crate::{countdown::{self, Countdown}, modify_config_automatically, schedule, thermostats, toast, write_json, Api, AppWindow, Functions, Singletons, Texts, ThermostatConfig};
+
+thread_local! {
+    static HOLD: Cell<Option<Hold>> = const { Cell::new(None) };
+    static COUNTDOWN: Countdown = Countdown::new(|singletons, time_left| singletons.set_hold_countdown(time_left), countdown::hours_minutes);
+}
+
+/// A temporary target temperature, restored to what the schedule or the default dictates once it ends.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy)]
+struct Hold {
+    endpoint: usize,
+    until: DateTime<Local>,
+}
+
+/// Loads the hold that was in progress when the last session ended, if any.
+pub fn load(path: &Path) {
+    let Ok(contents) = fs::read_to_string(path) else { return };
+
+    match serde_json::from_str::<Hold>(&contents) {
+        Ok(hold) => HOLD.set(Some(hold)),
+        Err(err) => error!("Could not read temperature hold from disk: {:?}", err),
+    }
+}
+
+/// Saves the hold in progress, or removes the saved one if there is none.
+pub fn save(path: &Path) -> Result<()> {
+    match HOLD.get() {
+        Some(hold) => write_json(&hold, path),
+        None if path.exists() => Ok(fs::remove_file(path)?),
+        None => Ok(()),
+    }
+}
+
+/// Continues the countdown of a hold loaded from disk.
+/// One that ended while the app was closed ends as soon as the thermostat responds.
+pub fn resume(ui: &AppWindow, api: &Api) {
+    if let Some(hold) = HOLD.get() {
+        info!("Resuming temperature hold until {}", hold.until.format("%H:%M"));
+        start_countdown(ui, api);
+    }
+}
+
+pub fn is_active() -> bool {
+    HOLD.get().is_some()
+}
+
+/// Holds the current target temperature for `hold_minutes`, or cancels the hold in progress and keeps the current target.
+pub fn toggle(ui: &AppWindow, api: &Api) {
+    if is_active() {
+        info!("Canceled temperature hold");
+        HOLD.set(None);
+        COUNTDOWN.with(|countdown| countdown.stop(ui));
+        toast::show_info(ui, &ui.global::<Texts>().invoke_hold_canceled());
+        return;
+    }
+
+    let options = ui.global::<Singletons>().get_options();
+    if options.read_only {
+        return; // Could never restore the target.
+    }
+
+    let minutes = options.hold_minutes;
+    info!("Holding the target temperature for {} minutes", minutes);
+    HOLD.set(Some(Hold { endpoint: thermostats::selected(), until: Local::now() + Duration::from_secs(minutes as u64 * 60) }));
+    start_countdown(ui, api);
+
+    let target_temp = ui.global::<Functions>().invoke_format_temp(ui.global::<Singletons>().get_config().target_temp);
+    toast::show_info(ui, &ui.global::<Texts>().invoke_holding(target_temp, minutes));
+}
+
+fn start_countdown(ui: &AppWindow, api: &Api) {
+    let Some(hold) = HOLD.get() else { return };
+    let until = Instant::now() + (hold.until - Local::now()).to_std().unwrap_or_default(); // Ended already if negative.
+
+    let api = api.clone();
+    COUNTDOWN.with(|countdown| countdown.start(ui, until, move |ui| {
+        let Some(hold) = HOLD.get() else { return true };
+        if thermostats::confirmed_config(hold.endpoint).is_none() {
+            return false; // Do not send anything based on a config that has not been fetched yet.
+        }
+
+        HOLD.set(None);
+        if !thermostats::is_selected(hold.endpoint) {
+            warn!("Temperature hold ended, but another thermostat is selected since; keeping its target");
+            return true;
+        }
+
+        let now = Local::now().naive_local();
+        let target_temp = schedule::target_at(now)
+            .or_else(|| schedule::weekday_target(now))
+            .unwrap_or_else(|| ui.global::<Singletons>().get_options().default_target_temp);
+        info!("Temperature hold ended, restoring the target to {}", target_temp);
+        modify_config_automatically(ui, &api, |cfg: &mut ThermostatConfig| cfg.target_temp = target_temp);
+        let texts = ui.global::<Texts>();
+        toast::show_automatic(ui, &texts.invoke_hold_ended(), &texts.invoke_target_back_to(ui.global::<Functions>().invoke_format_temp(target_temp)));
+        true
+    }));
+}
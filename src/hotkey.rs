@@ -0,0 +1,56 @@
+use std::cell::RefCell;
+use global_hotkey::{hotkey::HotKey, GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+use log::{error, info, warn};
+use slint::ComponentHandle;
+
+use crate::{hide_window, tray::show_window, AppWindow};
+
+thread_local! {
+    // Dropping the manager unregisters the hotkey, so it has to live as long as the UI.
+    static MANAGER: RefCell<Option<GlobalHotKeyManager>> = const { RefCell::new(None) };
+}
+
+/// Registers the system-wide hotkey that shows and hides the window, e.g. `Ctrl+Alt+T`.
+/// An empty string disables it. Failures, like another app already using it, are logged and otherwise ignored.
+pub fn register_hotkey(ui: &AppWindow, hotkey_str: &str) {
+    if hotkey_str.is_empty() {
+        return;
+    }
+
+    let hotkey: HotKey = match hotkey_str.parse() {
+        Ok(hotkey) => hotkey,
+        Err(err) => {
+            error!("Invalid hotkey {:?}: {}", hotkey_str, err);
+            return;
+        },
+    };
+
+    let manager = match GlobalHotKeyManager::new() {
+        Ok(manager) => manager,
+        Err(err) => {
+            error!("Could not set up global hotkeys: {}", err);
+            return;
+        },
+    };
+
+    if let Err(err) = manager.register(hotkey) {
+        warn!("Could not register hotkey {}, another app may already be using it: {}", hotkey_str, err);
+        return;
+    }
+    info!("Registered hotkey {} to show and hide the window", hotkey_str);
+
+    let ui_handle = ui.as_weak();
+    GlobalHotKeyEvent::set_event_handler(Some(move |event: GlobalHotKeyEvent| {
+        if event.id == hotkey.id() && event.state == HotKeyState::Pressed {
+            let _ = ui_handle.upgrade_in_event_loop(|ui| {
+                if ui.window().is_visible() {
+                    hide_window(&ui);
+                } else {
+                    show_window(&ui);
+                }
+            });
+        }
+    }));
+
+    MANAGER.with_borrow_mut(|m| *m = Some(manager));
+}
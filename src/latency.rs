@@ -0,0 +1,48 @@
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+use log::debug;
+use slint::ComponentHandle;
+
+use crate::{AppWindow, Singletons};
+
+const WINDOW: usize = 20; // Number of recent requests the average is taken over.
+
+/// Durations of the most recent API requests, recorded from any thread.
+static LATENCY: Mutex<Latency> = Mutex::new(Latency { samples: VecDeque::new() });
+
+struct Latency {
+    samples: VecDeque<Duration>,
+}
+
+impl Latency {
+    fn record(&mut self, elapsed: Duration) {
+        self.samples.push_back(elapsed);
+        while self.samples.len() > WINDOW {
+            self.samples.pop_front();
+        }
+    }
+
+    fn average(&self) -> Duration {
+        if self.samples.is_empty() {
+            return Duration::ZERO;
+        }
+
+        self.samples.iter().sum::<Duration>() / self.samples.len() as u32
+    }
+}
+
+/// Records how long a single attempt of an API request took, whether it succeeded or not.
+pub fn record(method: &str, thermostat: &str, elapsed: Duration) {
+    debug!("{} request to {} took {} ms", method, thermostat, elapsed.as_millis());
+    LATENCY.lock().unwrap().record(elapsed);
+}
+
+/// Shows the latest and average latency, and the slowest request among them, in the debug overlay.
+pub fn update_overlay(ui: &AppWindow) {
+    let latency = LATENCY.lock().unwrap();
+    let singletons = ui.global::<Singletons>();
+
+    let millis = |duration: Duration| duration.as_millis() as i32;
+    singletons.set_latency_last_ms(latency.samples.back().copied().map_or(-1, millis));
+    singletons.set_latency_average_ms(millis(latency.average()));
+    singletons.set_latency_max_ms(latency.samples.iter().copied().max().map_or(-1, millis));
+}
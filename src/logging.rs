@@ -0,0 +1,121 @@
+use std::{fs::{self, File, OpenOptions}, io::{self, Write}, path::{Path, PathBuf}, sync::Mutex};
+use chrono::Local;
+use env_logger::{fmt::Formatter, Env, Target};
+use log::{kv::{self, Key, VisitSource}, Record};
+use serde_json::{Map, Value};
+
+const LOG_FILE: &str = "thermostat.log";
+const FORMAT_VAR: &str = "THERMOSTAT_LOG_FORMAT";
+const MAX_SIZE: u64 = 1024 * 1024; // Rotate once the log reaches 1 MiB.
+const KEEP: u32 = 2; // Number of rotated logs kept next to the current one.
+
+/// The log file, only known once the data dir has been determined.
+static FILE: Mutex<Option<RotatingFile>> = Mutex::new(None);
+
+/// Sets up logging. Records go to stderr in debug builds and to the log file once it is opened.
+/// `RUST_LOG` still works as usual, the default is info for the app itself.
+/// With `THERMOSTAT_LOG_FORMAT=json`, every record is written as a JSON object on a line of its own.
+pub fn init() {
+    let mut builder = env_logger::Builder::from_env(Env::default().default_filter_or("Thermostat=info"));
+    if std::env::var(FORMAT_VAR).is_ok_and(|format| format.eq_ignore_ascii_case("json")) {
+        builder.format(write_json);
+    }
+    builder.target(Target::Pipe(Box::new(LogWriter))).init();
+}
+
+/// Writes a record as a single line of JSON, with its key-values, e.g. `api_error`, as fields of their own.
+fn write_json(buf: &mut Formatter, record: &Record) -> io::Result<()> {
+    let mut fields = Map::new();
+    fields.insert("timestamp".to_owned(), Local::now().to_rfc3339().into());
+    fields.insert("level".to_owned(), record.level().as_str().into());
+    fields.insert("target".to_owned(), record.target().into());
+    fields.insert("message".to_owned(), record.args().to_string().into());
+    let _ = record.key_values().visit(&mut JsonFields(&mut fields));
+
+    writeln!(buf, "{}", Value::Object(fields))
+}
+
+struct JsonFields<'a>(&'a mut Map<String, Value>);
+
+impl<'kvs> VisitSource<'kvs> for JsonFields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        self.0.insert(key.to_string(), value.to_string().into());
+        Ok(())
+    }
+}
+
+/// Starts writing the log to a file in the data dir.
+pub fn open_file(data_dir: &Path) -> io::Result<()> {
+    let file = RotatingFile::open(data_dir.join(LOG_FILE))?;
+    *FILE.lock().unwrap() = Some(file);
+    Ok(())
+}
+
+/// Writes log records to stderr in debug builds and to the log file, if any.
+/// Failures are ignored, there is nowhere left to report them.
+struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if cfg!(debug_assertions) {
+            let _ = io::stderr().write_all(buf);
+        }
+
+        if let Some(file) = FILE.lock().unwrap().as_mut() {
+            let _ = file.write(buf);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(()) // Records are written straight to the file.
+    }
+}
+
+/// A log file that is moved to `<name>.1` once it grows too large, shifting older ones up to `<name>.KEEP`.
+struct RotatingFile {
+    path: PathBuf,
+    file: Option<File>, // Closed while rotating, as Windows cannot rename open files.
+    size: u64,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { path, file: Some(file), size })
+    }
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<()> {
+        if self.size > 0 && self.size + buf.len() as u64 > MAX_SIZE {
+            self.rotate()?;
+        }
+
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => self.file.insert(File::create(&self.path)?), // Reopen after a failed rotation.
+        };
+        file.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file = None;
+
+        for i in (1..KEEP).rev() {
+            let _ = fs::rename(self.rotated_path(i), self.rotated_path(i + 1)); // Older logs may not exist yet.
+        }
+        fs::rename(&self.path, self.rotated_path(1))?;
+
+        self.file = Some(File::create(&self.path)?);
+        self.size = 0;
+        Ok(())
+    }
+
+    fn rotated_path(&self, index: u32) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        path.into()
+    }
+}
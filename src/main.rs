@@ -1,308 +1,1931 @@
 #![cfg_attr(all(target_os = "windows", not(debug_assertions)), windows_subsystem = "windows")] // Hide console window on Windows if we're not debugging.
 #![allow(non_snake_case)] // The project name is also the name of the process, which should have a capital T.
 
-use slint::{private_unstable_api::re_exports::{EventResult, KeyEvent}, WindowPosition, PhysicalPosition, Weak};
-use tokio::{task::JoinHandle, time::{sleep, Instant}};
-use std::{fs, path::{Path, PathBuf}, io::{BufWriter, Write}, time::Duration};
+use slint::{private_unstable_api::re_exports::{EventResult, KeyEvent}, winit_030::WinitWindowAccessor, WindowPosition, WindowSize, PhysicalPosition, PhysicalSize, SharedString, Weak};
+use tokio::{sync::Notify, task::JoinHandle, time::{sleep, Instant, Interval}};
+use std::{cell::{Cell, RefCell}, collections::HashMap, fs, future::Future, path::{Path, PathBuf}, io::BufWriter, sync::{atomic::{AtomicBool, AtomicU64, Ordering}, Arc}, time::Duration};
 use directories::ProjectDirs;
-use anyhow::Result;
-use log::{error, info};
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use chrono::Weekday;
+use log::{error, info, warn};
+use futures_util::{future::join_all, StreamExt};
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+use reqwest::{header::{HeaderMap, HeaderValue, AUTHORIZATION}, Identity, NoProxy, Proxy, StatusCode};
 
 slint::include_modules!();
 
+mod autostart;
+mod boost;
+mod clipboard;
+mod clock;
+mod countdown;
+mod force;
+mod frost;
+mod history;
+mod hold;
+mod latency;
+mod logging;
+mod metrics;
+mod migrate;
+mod mock;
+mod notifications;
+mod runtime;
+mod schedule;
+mod schema;
+mod shortcuts;
+mod theme;
+mod thermostats;
+mod toast;
+mod trend;
+mod undo;
+#[cfg(windows)]
+mod tray;
+#[cfg(windows)]
+mod hotkey;
+use mock::MockApi;
+use schedule::ScheduleEntry;
+use shortcuts::Shortcut;
+use thermostats::ThermostatEndpoint;
+
 #[cfg(not(debug_assertions))]
-const API_URL: &str = "http://192.168.178.48:5567/";
+const DEFAULT_API_URL: &str = "http://192.168.178.48:5567/";
 #[cfg(debug_assertions)]
-const API_URL: &str = "http://192.168.178.48:5568/";
+const DEFAULT_API_URL: &str = "http://192.168.178.48:5568/";
 const OPTIONS_FILE: &str = "options.json";
+const STATE_FILE: &str = "state.json";
+const HISTORY_FILE: &str = "history.json";
+const RUNTIME_FILE: &str = "runtime.json";
+const HOLD_FILE: &str = "hold.json";
+
+const DEFAULT_WINDOW_POS: PhysicalPosition = PhysicalPosition { x: 190, y: 190 }; // Only used if the monitors are not known.
+const DEFAULT_WINDOW_MARGIN: f64 = 40.0; // In logical pixels, distance to the top right corner of the primary monitor on first launch.
+const EDGE_SNAP_PX: i32 = 20; // Distance to a monitor edge within which the window snaps to it.
+const MIN_VISIBLE_PX: i32 = 50; // How much of the window has to be on a monitor to count as reachable.
+const DEFAULT_OPACITY_FOCUSED: f32 = 0.9;
+const DEFAULT_OPACITY_UNFOCUSED: f32 = 0.35;
+const DEFAULT_OPACITY_FADE_MS: i32 = 250;
+
+const DEFAULT_TEMPERATURE_STEP: f32 = 0.5;
+const DEFAULT_MIN_TARGET_TEMP: f32 = 5.0;
+const DEFAULT_MAX_TARGET_TEMP: f32 = 30.0;
+const DEFAULT_TARGET_TEMP: f32 = 20.0; // Target to reset to
+const SCROLL_SHIFT_MULTIPLIER: f32 = 2.0; // Step multiplier when scrolling while holding shift.
+const DEFAULT_HOTKEY: &str = "Ctrl+Alt+T";
+const DEFAULT_CO2_ALERT_MARGIN: i32 = 100; // In ppm
+const MIN_CO2_TARGET: i32 = 400; // In ppm, about outdoor air, so anything lower can never be reached.
+const MAX_CO2_TARGET: i32 = 2000; // In ppm
+const DEFAULT_CO2_TARGET: i32 = 500; // In ppm
+const DEFAULT_CO2_PRESETS: [i32; 3] = [500, 700, 1000]; // In ppm
+const DEFAULT_DEBOUNCE_MS: i32 = 250;
+const DEFAULT_TOGGLE_COOLDOWN_MS: i32 = 2000;
+const DEFAULT_CO2_GOOD_BELOW: i32 = 800;
+const DEFAULT_CO2_POOR_ABOVE: i32 = 1200;
+const DEFAULT_HOLD_MINUTES: i32 = 120;
+const DEFAULT_MAX_UPDATES_PER_MINUTE: i32 = 30;
+const DEFAULT_FROST_PROTECTION_TEMP: f32 = 8.0;
+const DEFAULT_BOOST_DELTA: f32 = 2.0;
+const DEFAULT_BOOST_MINUTES: i32 = 30;
+const DEFAULT_MAX_CLOCK_SKEW_SECS: i32 = 120;
+const MAX_CO2_SMOOTHING: f32 = 0.95; // Any closer to 1 and the shown CO₂ would hardly move.
+const DEFAULT_POLL_INTERVAL_SECS: i32 = 15;
+const MIN_POLL_INTERVAL_SECS: i32 = 1; // Anything faster would just hammer the server.
+const MIN_UI_SCALE: f32 = 0.5;
+const MAX_UI_SCALE: f32 = 3.0;
+const UI_SCALE_STEP: f32 = 0.1;
+const HIGH_CONTRAST_SCALE: f32 = 1.25; // Larger text for the high contrast theme.
+const SHUTDOWN_FLUSH_TIMEOUT: Duration = Duration::from_secs(3); // How long quitting waits for the last config update.
+
+/// Command-line arguments, all optional.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    /// Directory to read and write options and other data in, instead of the default data dir.
+    #[arg(long)]
+    data_dir: Option<PathBuf>,
+    /// API URL to use for this run only, instead of the thermostats in the options.
+    #[arg(long)]
+    api_url: Option<String>,
+    /// Write the current options to this file and exit, e.g. to move them to another machine.
+    #[arg(long, value_name = "FILE")]
+    export_options: Option<PathBuf>,
+    /// Replace the options with those in this file, as written by --export-options, and start with them.
+    #[arg(long, value_name = "FILE", conflicts_with = "export_options")]
+    import_options: Option<PathBuf>,
+    /// Talk to a simulated thermostat instead of the real ones, to work on the UI without a server.
+    #[arg(long)]
+    mock: bool,
+}
+
+/// Incremented every time the config is changed locally, so retries and rollbacks of outdated updates can be dropped.
+static CONFIG_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    // Only ever touched from the UI thread.
+    static CONFIG_UPDATES: RefCell<ConfigUpdates> = RefCell::new(ConfigUpdates::default());
+    static NATIVE_SCALE_FACTOR: Cell<Option<f32>> = const { Cell::new(None) }; // Of the monitor, before the UI scale is applied.
+}
+
+/// Config updates that have not completed yet, see `queue_config_update`.
+#[derive(Default)]
+struct ConfigUpdates {
+    scheduled: Option<JoinHandle<()>>, // Waiting for the user to stop modifying.
+    in_flight: Option<(ThermostatConfig, JoinHandle<()>)>, // Sending the PATCH request.
+    queued: Option<QueuedUpdate>, // Could not reach the API, waiting for the connection to come back.
+    last: Option<Instant>, // When the config was last changed.
+    toggles: ToggleCooldown,
+    limiter: RateLimiter,
+}
+
+/// Token bucket limiting the number of updates per minute, allowing bursts of up to that many.
+#[derive(Default)]
+struct RateLimiter {
+    tokens: f64,
+    refilled: Option<Instant>, // Full until the first update.
+}
+
+impl RateLimiter {
+    fn refill(&mut self, per_minute: i32) {
+        let capacity = per_minute as f64;
+        self.tokens = match self.refilled {
+            Some(refilled) => (self.tokens + refilled.elapsed().as_secs_f64() * capacity / 60.0).min(capacity),
+            None => capacity,
+        };
+        self.refilled = Some(Instant::now());
+    }
+
+    /// How long until an update may be sent, zero if right away. A limit of 0 or less never throttles.
+    fn wait(&mut self, per_minute: i32) -> Duration {
+        if per_minute <= 0 {
+            return Duration::ZERO;
+        }
+
+        self.refill(per_minute);
+        Duration::from_secs_f64(((1.0 - self.tokens) * 60.0 / per_minute as f64).max(0.0))
+    }
+
+    fn take(&mut self, per_minute: i32) {
+        if per_minute > 0 {
+            self.refill(per_minute);
+            self.tokens = (self.tokens - 1.0).max(0.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod rate_limiter_tests {
+    use super::*;
+
+    /// Pretends the last refill was `secs` earlier.
+    fn refilled_ago(limiter: &mut RateLimiter, secs: u64) {
+        limiter.refilled = limiter.refilled.map(|refilled| refilled - Duration::from_secs(secs));
+    }
+
+    #[test]
+    fn allows_a_full_burst_before_throttling() {
+        let mut limiter = RateLimiter::default();
+        for _ in 0..3 {
+            assert_eq!(limiter.wait(3), Duration::ZERO);
+            limiter.take(3);
+        }
+
+        let wait = limiter.wait(3);
+        assert!(wait > Duration::from_secs(19) && wait <= Duration::from_secs(20), "waits for one token at 3 per minute, not {:?}", wait);
+    }
+
+    #[test]
+    fn refills_over_time() {
+        let mut limiter = RateLimiter::default();
+        for _ in 0..3 {
+            limiter.take(3);
+        }
 
-const WINDOW_OPACITY_FOCUSED: f32 = 0.9;
-const WINDOW_OPACITY_UNFOCUSED: f32 = 0.35;
+        refilled_ago(&mut limiter, 10);
+        assert!(limiter.wait(3) > Duration::ZERO, "half a token is not enough");
+        refilled_ago(&mut limiter, 10);
+        assert_eq!(limiter.wait(3), Duration::ZERO);
+    }
+
+    #[test]
+    fn refills_no_further_than_the_limit() {
+        let mut limiter = RateLimiter::default();
+        limiter.take(3);
+        refilled_ago(&mut limiter, 3600);
+        for _ in 0..3 {
+            limiter.take(3);
+        }
+        assert!(limiter.wait(3) > Duration::ZERO);
+    }
+
+    #[test]
+    fn never_throttles_without_a_limit() {
+        let mut limiter = RateLimiter::default();
+        for _ in 0..100 {
+            limiter.take(0);
+        }
+        assert_eq!(limiter.wait(0), Duration::ZERO);
+        assert_eq!(limiter.wait(-1), Duration::ZERO);
+    }
+}
 
-const TEMPERATURE_STEP: f32 = 0.5;
+/// When force and the master switch were last toggled, to keep the heater from being switched on and off in quick succession.
+#[derive(Default)]
+struct ToggleCooldown {
+    previous: Option<(usize, Config)>, // Config of the last update and its thermostat.
+    force: Option<Instant>,
+    master_switch: Option<Instant>,
+}
+
+impl ToggleCooldown {
+    /// Undoes toggles that come within `cooldown` of the previous toggle of the same switch.
+    /// Returns whether something was undone.
+    fn apply(&mut self, endpoint: usize, cfg: &mut Config, cooldown: Duration) -> bool {
+        let mut undone = false;
+        if let Some((_, previous)) = self.previous.as_ref().filter(|(previous_endpoint, _)| *previous_endpoint == endpoint) {
+            let now = Instant::now();
+            for (value, previous, last) in [(&mut cfg.force, previous.force, &mut self.force), (&mut cfg.master_switch, previous.master_switch, &mut self.master_switch)] {
+                if *value == previous {
+                    continue;
+                }
+
+                if last.is_some_and(|last| now - last < cooldown) {
+                    *value = previous;
+                    undone = true;
+                } else {
+                    *last = Some(now);
+                }
+            }
+        }
+
+        self.previous = Some((endpoint, cfg.clone()));
+        undone
+    }
+}
+
+/// A config update that could not reach the API, sent again once the API can be reached.
+#[derive(Debug, Clone, Copy)]
+struct QueuedUpdate {
+    endpoint: usize,
+    cfg: ThermostatConfig,
+    base: Option<ThermostatConfig>, // Config of the thermostat when the update failed, to notice changes made elsewhere since.
+}
+
+/// Whether the window is shown, polling pauses while it is hidden.
+static WINDOW_SHOWN: AtomicBool = AtomicBool::new(true);
+static WINDOW_SHOWN_NOTIFY: Notify = Notify::const_new(); // Wakes the poller when the window is shown again.
+
+/// Seconds between polls of the API, set from the options on startup. Also what tells whether updates stopped.
+static POLL_INTERVAL_SECS: AtomicU64 = AtomicU64::new(DEFAULT_POLL_INTERVAL_SECS as u64);
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    env_logger::init();
+    logging::init();
+    let args = Args::parse();
 
-    // Get data dir, if possible.
-    // Default to current directory.
-    let app_dir = ProjectDirs::from("com", "PTSMods", "Thermostat");
-    let mut data_dir = app_dir.map_or_else(|| std::env::current_dir().unwrap().to_owned(), |pds| pds.data_dir().to_owned());
-    
-    if let Err(e) = fs::create_dir_all(&data_dir) {
-        error!("Could not create data dir: {:?}", e);
-        data_dir = std::env::current_dir().unwrap(); // Fallback to current directory.
+    let data_dir = match &args.data_dir {
+        Some(data_dir) => {
+            // Explicitly asked for, so do not silently fall back to another directory.
+            fs::create_dir_all(data_dir)?;
+            data_dir.clone()
+        },
+        None => default_data_dir(),
+    };
+
+    if let Err(err) = logging::open_file(&data_dir) {
+        error!("Could not open log file: {:?}", err);
+    }
+    if args.data_dir.is_some() {
+        info!("Using data dir {:?} from the command line", data_dir);
     }
     info!("Data dir: {:?}", data_dir);
 
     let options_path = data_dir.join(OPTIONS_FILE);
+    let state_path = data_dir.join(STATE_FILE);
+    let history_path = data_dir.join(HISTORY_FILE);
+    let runtime_path = data_dir.join(RUNTIME_FILE);
+    let hold_path = data_dir.join(HOLD_FILE);
+
+    // Read options from disk, or from the file to import.
+    let mut options = match &args.import_options {
+        Some(path) => {
+            let options = migrate::import_options(path).with_context(|| format!("Could not import options from {:?}", path))?;
+            if options_path.exists() {
+                migrate::back_up(&options_path);
+            }
+            info!("Imported options from {:?}", path);
+            options
+        },
+        None => migrate::load_options(&options_path),
+    };
+    options.validate();
+
+    if let Some(path) = args.export_options {
+        save_options(&options, &path)?;
+        info!("Exported options to {:?}", path);
+        return Ok(());
+    }
+    if args.import_options.is_some() {
+        save_options(&options, &options_path)?; // Also saved on exit, but do not lose the import if the app does not get that far.
+    }
+    autostart::apply(options.app_options.auto_start);
+    POLL_INTERVAL_SECS.store(options.app_options.poll_interval_secs as u64, Ordering::SeqCst);
 
-    // Read options from disk.
-    let options = 
-        if Path::new(&options_path).exists() { Some(fs::read_to_string(&options_path)?) }
-        else { None };
-    let options = options
-        .map(|s| serde_json::from_str(&s))
-        .unwrap_or(Ok(Options::default()));
+    if let Some(api_url) = args.api_url {
+        if !is_valid_api_url(&api_url) {
+            bail!("Invalid API URL {:?}, expected an http or https URL", api_url);
+        }
 
-    if let Err(err) = &options {
-        error!("Could not read options from disk: {:?}", err);
+        info!("Using API URL {} from the command line", api_url);
+        options.api_url_override = Some(api_url);
     }
-    let options = options.unwrap();
+    options.mock = args.mock;
+    options.first_run = args.import_options.is_none() && !options_path.exists();
 
     // Run the UI.
     let ui = AppWindow::new()?;
     ui.set_is_preview(false); // Disable preview mode.
     ui.global::<Singletons>().set_options(options.app_options.clone());
-    run_ui(ui, options, &options_path).await
+    ui.set_window_opacity(options.app_options.opacity_focused); // Focused on startup
+    theme::watch_system_theme(&ui);
+
+    // Show the last known config and state until the API responds.
+    if let Some(cached) = load_cached_state(&state_path) {
+        let singletons = ui.global::<Singletons>();
+        singletons.set_config(cached.config.into());
+        singletons.set_confirmed_config(cached.config.into());
+        singletons.set_state(cached.state.into());
+        ui.invoke_hide_splash();
+    }
+
+    history::load(&history_path);
+    history::update_graph(&ui);
+    runtime::load(&runtime_path);
+    runtime::update_display(&ui);
+    hold::load(&hold_path);
+
+    run_ui(ui, options, &options_path, &state_path).await?;
+
+    if let Err(err) = history::save(&history_path) {
+        error!("Could not save temperature history to disk: {:?}", err);
+    }
+    if let Err(err) = runtime::save(&runtime_path) {
+        error!("Could not save heating runtime to disk: {:?}", err);
+    }
+    if let Err(err) = hold::save(&hold_path) {
+        error!("Could not save temperature hold to disk: {:?}", err);
+    }
+    Ok(())
 }
 
-/// Registers event handlers and runs the UI.
-async fn run_ui(ui: AppWindow, mut options: Options, options_path: &PathBuf) -> Result<()> {
-    // Acquire the config and state from the API asynchronously.
-    let ui_handle = ui.as_weak();
-    tokio::spawn(async move {
-        let resp = get_api_async(true).await;
+/// Gets the data dir: the one the OS designates for app data, or else the directory of the executable, or else a temporary one.
+/// Each is only used if files can actually be written to it, so the options are not silently lost on exit.
+fn default_data_dir() -> PathBuf {
+    let candidates = [
+        ("app data dir", ProjectDirs::from("com", "PTSMods", "Thermostat").map(|pds| pds.data_dir().to_owned())),
+        ("executable dir", std::env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf))),
+        ("temporary dir", Some(std::env::temp_dir().join("Thermostat"))),
+    ];
 
-        let _ = ui_handle.upgrade_in_event_loop(move |ui| {
-            if let Ok(resp) = resp {
-                if !resp.success {
-                    error!("API returned an error: {}", resp.error.unwrap());
-                    return;
+    for (kind, data_dir) in candidates {
+        let Some(data_dir) = data_dir else {
+            warn!("There is no {}, trying the next option", kind);
+            continue;
+        };
+
+        match check_writable(&data_dir) {
+            Ok(()) => {
+                if kind != "app data dir" {
+                    warn!("Falling back to the {} {:?} as data dir", kind, data_dir);
                 }
-                
-                let singletons = ui.global::<Singletons>();
-                let data = resp.data.unwrap();
-                singletons.set_config(data.config.unwrap().into());
-                singletons.set_state(data.state.into());
+                return data_dir;
+            },
+            Err(err) => error!("Cannot write to the {} {:?}: {:?}", kind, data_dir, err),
+        }
+    }
 
-                // Hide the splash window.
-                ui.invoke_hide_splash();
-            }
-        });
-    });
+    // Nothing is writable, nothing is saved then, but the app still works.
+    error!("No writable data dir found, options will not be saved");
+    std::env::temp_dir()
+}
+
+/// Creates `dir` if needed and checks that files can be written to it.
+fn check_writable(dir: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let probe = dir.join(".write-test");
+    fs::write(&probe, [])?;
+    fs::remove_file(probe)
+}
+
+/// Registers event handlers and runs the UI.
+async fn run_ui(ui: AppWindow, mut options: Options, options_path: &Path, state_path: &Path) -> Result<()> {
+    // Created once and shared by the poller and all config updates, so they reuse pooled connections.
+    let api = Api::new(&options)?;
+    for endpoint in api.endpoints.iter() {
+        info!("Using API of {} at {} with a timeout of {:?}", endpoint.name, endpoint.api_url, api.timeout);
+        if api.mock.is_none() {
+            let api_url = endpoint.api_url.clone();
+            tokio::spawn(async move { log_resolved_host(&api_url).await });
+        }
+    }
+
+    thermostats::init(&ui, &api, options.selected_endpoint(), options.last_co2_targets.clone());
+    shortcuts::init(&ui, &options.keymap);
+    select_language(&options.app_options.language);
+    metrics::init(options.metrics_file.clone());
+    fetch_full_state(&ui, api.clone(), thermostats::selected());
 
     // Register event handlers
-    register_target_temp_handler(&ui);
+    register_target_temp_handler(&ui, api.clone());
+    register_scroll_handler(&ui, api.clone());
     register_window_move_handler(&ui);
     register_quit_handler(&ui);
-    register_key_handler(&ui);
+    register_key_handler(&ui, api.clone(), options_path.to_owned(), options.co2_presets.clone());
     register_focus_handler(&ui);
+    register_thermostat_handler(&ui, api.clone());
+    register_latency_handler(&ui);
+    register_freshness_handler(&ui);
+    register_retry_handler(&ui, api.clone());
+    register_splash_timeout_handler(&ui, api.clone());
+
+    for endpoint in 0..api.endpoints.len() {
+        start_live_feed(&ui, api.clone(), endpoint);
+    }
+    schedule::start_scheduler(&ui, api.clone(), options.schedule.clone());
+    schedule::start_weekday_defaults(&ui, api.clone(), options.weekday_targets.clone());
+    hold::resume(&ui, &api);
+    start_ui_updater(&ui, api.clone());
+
+    // With a tray icon, hiding the window keeps the app running until it is quit from the tray.
+    #[cfg(windows)]
+    let has_tray = tray::create_tray(&ui, api.clone())
+        .map_err(|err| error!("Could not create tray icon: {:?}", err))
+        .is_ok();
+    #[cfg(not(windows))]
+    let has_tray = false;
 
-    start_ui_updater(&ui);
+    #[cfg(windows)]
+    hotkey::register_hotkey(&ui, &ui.global::<Singletons>().get_options().hotkey);
 
     // Restore previous window position
     ui.window().set_position(WindowPosition::Physical(options.window_pos));
-    ui.run()?;
+    ui.show()?;
+    // Only now the monitors are known.
+    apply_ui_scale(&ui);
+    restore_window_size(&ui, options.window_size);
+    apply_click_through(&ui);
+    ui.window().with_winit_window(|window| {
+        // For pinning the window to one of them.
+        info!("Monitors: {:?}", window.available_monitors().map(|monitor| monitor.name().unwrap_or_default()).collect::<Vec<_>>());
+    });
+    match &options.monitor {
+        Some(pinned) => pin_to_monitor(&ui, pinned),
+        None if options.first_run => {
+            if let Some(pos) = default_window_pos(&ui) {
+                info!("First launch, placing the window at {},{}", pos.x, pos.y);
+                ui.window().set_position(WindowPosition::Physical(pos));
+            }
+        },
+        None => ensure_on_screen(&ui),
+    }
+    if has_tray {
+        slint::run_event_loop_until_quit()?;
+    } else {
+        slint::run_event_loop()?;
+        ui.hide()?;
+    }
     
     // Save options upon shutdown.
     options.window_pos = ui.window().position();
+    options.window_size = ui.window().size();
     options.app_options = ui.global::<Singletons>().get_options();
+    options.last_co2_targets = thermostats::last_co2_targets();
+    if options.api_url_override.is_none() {
+        options.selected_thermostat = thermostats::selected(); // The override is the only one there is.
+    }
     save_options(&options, options_path)?;
 
+    // Only cache data that actually came from the API this session.
+    let singletons = ui.global::<Singletons>();
+    if !singletons.get_stale() {
+        let cached = CachedState { config: singletons.get_config().into(), state: singletons.get_state().into() };
+        if let Err(err) = write_json(&cached, state_path) {
+            error!("Could not cache state to disk: {:?}", err);
+        }
+    }
+
+    flush_config_updates(&ui, &api).await;
+
+    Ok(())
+}
+
+/// Delivers the last config change if it was still waiting to be sent or in flight when the app was quit.
+/// Gives up after `SHUTDOWN_FLUSH_TIMEOUT`, so an unreachable server does not keep the app from exiting.
+async fn flush_config_updates(ui: &AppWindow, api: &Api) {
+    let (scheduled, in_flight, queued) = CONFIG_UPDATES.with_borrow_mut(|updates| (updates.scheduled.take(), updates.in_flight.take(), updates.queued.take()));
+    if let Some(queued) = queued {
+        warn!("Dropped config update {:?} as the API could not be reached since", queued.cfg);
+    }
+
+    let (cfg, jh) = match scheduled {
+        // The event loop has stopped, so the scheduled update can no longer pick up the config. Send it from here instead.
+        Some(scheduled) if !scheduled.is_finished() => {
+            scheduled.abort();
+            if let Some((_, previous)) = in_flight {
+                previous.abort(); // Overwritten by this one anyway.
+            }
+
+            let cfg: ThermostatConfig = ui.global::<Singletons>().get_config().into();
+            let api = api.clone();
+            let endpoint = thermostats::selected();
+            let base = thermostats::confirmed_config(endpoint);
+            let generation = CONFIG_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+            (cfg, tokio::spawn(async move {
+                match patch_api_async(&api, endpoint, cfg, base, generation).await {
+                    Ok(resp) if !resp.success => error!(api_error = resp.error.unwrap().as_str(); "API returned an error"),
+                    Ok(_) => {},
+                    Err(err) => log_request_error("Error sending API request", &err),
+                }
+            }))
+        },
+        _ => match in_flight {
+            Some((cfg, jh)) if !jh.is_finished() => (cfg, jh),
+            _ => return,
+        },
+    };
+
+    info!("Waiting for config update {:?} to be delivered before exiting", cfg);
+    if tokio::time::timeout(SHUTDOWN_FLUSH_TIMEOUT, jh).await.is_err() {
+        warn!("Dropped config update {:?} as it was not delivered within {:?}", cfg, SHUTDOWN_FLUSH_TIMEOUT);
+    }
+}
+
+/// Restores the window size of the last session, limited to the bounds of the monitor the window is on.
+fn restore_window_size(ui: &AppWindow, size: PhysicalSize) {
+    if size.width == 0 || size.height == 0 {
+        warn!("Ignoring saved window size {}x{}", size.width, size.height);
+        return;
+    }
+
+    let monitor = ui.window().with_winit_window(|window| window.current_monitor().map(|monitor| monitor.size())).flatten();
+    let size = match monitor {
+        Some(bounds) => PhysicalSize::new(size.width.min(bounds.width), size.height.min(bounds.height)),
+        None => size,
+    };
+    ui.window().set_size(WindowSize::Physical(size));
+}
+
+/// Moves the window onto the monitor it is pinned to, keeping it within the monitor's bounds.
+/// Falls back to the primary monitor if the pinned one is not connected.
+fn pin_to_monitor(ui: &AppWindow, pinned: &MonitorSelector) {
+    let pos = ui.window().position();
+    let size = ui.window().size();
+
+    let bounds = ui.window().with_winit_window(|window| {
+        let monitors: Vec<_> = window.available_monitors().collect();
+        let names: Vec<_> = monitors.iter().map(|monitor| monitor.name().unwrap_or_default()).collect();
+
+        let monitor = match pinned {
+            MonitorSelector::Index(i) => monitors.get(*i),
+            MonitorSelector::Name(name) => monitors.iter().zip(&names).find(|(_, n)| *n == name).map(|(monitor, _)| monitor),
+        };
+        let monitor = match monitor {
+            Some(monitor) => monitor.clone(),
+            None => {
+                warn!("Pinned monitor {:?} is not connected, using the primary monitor instead", pinned);
+                window.primary_monitor().or_else(|| monitors.first().cloned())?
+            },
+        };
+        Some((monitor.position(), monitor.size()))
+    }).flatten();
+
+    // Without monitor info there is nothing to place the window on, so leave it be.
+    let Some((origin, bounds)) = bounds else { return };
+    let clamp = |pos: i32, size: u32, origin: i32, bounds: u32| pos.clamp(origin, (origin + bounds as i32 - size as i32).max(origin));
+    let pinned_pos = PhysicalPosition::new(clamp(pos.x, size.width, origin.x, bounds.width), clamp(pos.y, size.height, origin.y, bounds.height));
+    if pinned_pos != pos {
+        info!("Moving the window from {},{} to {},{} on its pinned monitor", pos.x, pos.y, pinned_pos.x, pinned_pos.y);
+        ui.window().set_position(WindowPosition::Physical(pinned_pos));
+    }
+}
+
+/// Moves the window back to the default position if it is not on any monitor,
+/// e.g. because the monitor it was on last session is disconnected.
+fn ensure_on_screen(ui: &AppWindow) {
+    let pos = ui.window().position();
+    let size = ui.window().size();
+
+    let on_screen = ui.window().with_winit_window(|window| {
+        let mut monitors = window.available_monitors().peekable();
+        monitors.peek()?;
+
+        Some(monitors.any(|monitor| {
+            let (origin, bounds) = (monitor.position(), monitor.size());
+            let overlap_x = (pos.x + size.width as i32).min(origin.x + bounds.width as i32) - pos.x.max(origin.x);
+            let overlap_y = (pos.y + size.height as i32).min(origin.y + bounds.height as i32) - pos.y.max(origin.y);
+            overlap_x >= MIN_VISIBLE_PX && overlap_y >= MIN_VISIBLE_PX
+        }))
+    }).flatten();
+
+    // Without monitor info there is nothing to compare against, so leave the window be.
+    if on_screen == Some(false) {
+        let default_pos = default_window_pos(ui).unwrap_or(DEFAULT_WINDOW_POS);
+        info!("Window position {},{} is not on any monitor, moving the window to {},{}", pos.x, pos.y, default_pos.x, default_pos.y);
+        ui.window().set_position(WindowPosition::Physical(default_pos));
+    }
+}
+
+/// Top right corner of the primary monitor, inset by `DEFAULT_WINDOW_MARGIN`, where it is out of the way on any screen size.
+fn default_window_pos(ui: &AppWindow) -> Option<PhysicalPosition> {
+    let size = ui.window().size();
+    ui.window().with_winit_window(|window| {
+        let monitor = window.primary_monitor().or_else(|| window.available_monitors().next())?;
+        let (origin, bounds) = (monitor.position(), monitor.size());
+        let margin = (DEFAULT_WINDOW_MARGIN * monitor.scale_factor()) as i32;
+
+        let x = (origin.x + bounds.width as i32 - size.width as i32 - margin).max(origin.x);
+        Some(PhysicalPosition::new(x, origin.y + margin))
+    }).flatten()
+}
+
+fn register_target_temp_handler(ui: &AppWindow, api: Api) {
+    let ui_handle = ui.as_weak();
+    ui.on_request_config_change(move || {
+        let ui = ui_handle.unwrap();
+        let singletons = ui.global::<Singletons>();
+
+        // The controls are disabled in read-only mode, but never send anything that slipped through.
+        if singletons.get_options().read_only {
+            if let Some(cfg) = thermostats::confirmed_config(thermostats::selected()) {
+                singletons.set_config(cfg.into());
+            }
+            return;
+        }
+
+        undo::record(singletons.get_config().into());
+        queue_config_update(&ui, &api);
+    });
+}
+
+fn register_scroll_handler(ui: &AppWindow, api: Api) {
+    let ui_handle = ui.as_weak();
+    ui.on_request_temp_scroll(move |up: bool, shift: bool| {
+        let ui = ui_handle.unwrap();
+        let mut step = ui.global::<Functions>().invoke_temperature_step();
+        if shift {
+            step *= SCROLL_SHIFT_MULTIPLIER;
+        }
+
+        modify_config(&ui, &api, |cfg: &mut ThermostatConfig| {
+            cfg.target_temp += if up { step } else { -step };
+        });
+    });
+}
+
+fn register_window_move_handler(ui: &AppWindow) {
+    let ui_handle = ui.as_weak();
+    ui.on_request_window_move(move |dx: i32, dy: i32| {
+        let ui = ui_handle.unwrap();
+        let pos = ui.window().position(); // Current position
+
+        // Move the window along with the cursor.
+        let mut new_pos = PhysicalPosition { x: pos.x + dx, y: pos.y + dy };
+        if ui.global::<Singletons>().get_options().edge_snapping {
+            new_pos = snap_to_edges(&ui, new_pos);
+        }
+        ui.window().set_position(WindowPosition::Physical(new_pos));
+    });
+}
+
+/// Snaps a window position to the edges of the monitor the window is on when it is close to them.
+/// As the window keeps following the cursor, it comes loose again once dragged further.
+fn snap_to_edges(ui: &AppWindow, pos: PhysicalPosition) -> PhysicalPosition {
+    let size = ui.window().size();
+    let monitor = ui.window().with_winit_window(|window| window.current_monitor().map(|monitor| (monitor.position(), monitor.size()))).flatten();
+    let Some((origin, bounds)) = monitor else { return pos };
+
+    let snap = |pos: i32, len: u32, start: i32, monitor_len: u32| {
+        let end = start + monitor_len as i32 - len as i32; // Position at which the window touches the far edge.
+        if (pos - start).abs() <= EDGE_SNAP_PX {
+            start
+        } else if (pos - end).abs() <= EDGE_SNAP_PX {
+            end
+        } else {
+            pos
+        }
+    };
+
+    PhysicalPosition {
+        x: snap(pos.x, size.width, origin.x, bounds.width),
+        y: snap(pos.y, size.height, origin.y, bounds.height),
+    }
+}
+
+fn register_quit_handler(ui: &AppWindow) {
+    let ui_handle = ui.as_weak();
+    ui.on_request_quit(move || {
+        let ui = ui_handle.unwrap();
+        hide_window(&ui);
+    });
+}
+
+fn hide_window(ui: &AppWindow) {
+    let _ = ui.window().hide(); // We do not care about the result here.
+    set_window_shown(false);
+}
+
+/// Shows the labels in `language`, if set. Otherwise Slint already picked the system language, or English without a translation for it.
+fn select_language(language: &str) {
+    if language.is_empty() {
+        return;
+    }
+    match slint::select_bundled_translation(language) {
+        Ok(()) => info!("Showing labels in {}", language),
+        Err(err) => warn!("No translation for language {:?}, falling back to English: {}", language, err),
+    }
+}
+
+/// Makes the window ignore the mouse if `click_through` is on, so clicks reach the windows underneath.
+/// Returns false if that did not work, turning the option off if the platform does not support it.
+fn apply_click_through(ui: &AppWindow) -> bool {
+    let singletons = ui.global::<Singletons>();
+    let mut options = singletons.get_options();
+    let result = ui.window().with_winit_window(|window| window.set_cursor_hittest(!options.click_through));
+
+    match result {
+        Some(Ok(())) => true,
+        _ if !options.click_through => true, // Ignoring the mouse was never on then.
+        Some(Err(err)) => {
+            warn!("Could not make the window click-through: {}", err);
+            options.click_through = false;
+            singletons.set_options(options);
+            toast::show_warning(ui, &ui.global::<Texts>().invoke_click_through_unsupported());
+            false
+        },
+        None => false, // No window yet, applied once it is shown.
+    }
+}
+
+/// Tracks whether the window is shown, to pause polling while it is not.
+fn set_window_shown(shown: bool) {
+    WINDOW_SHOWN.store(shown, Ordering::SeqCst);
+    if shown {
+        WINDOW_SHOWN_NOTIFY.notify_waiters();
+    }
+}
+
+fn register_key_handler(ui: &AppWindow, api: Api, options_path: PathBuf, co2_presets: Vec<i32>) {
+    let ui_handle = ui.as_weak();
+    ui.on_key_pressed(move |e: KeyEvent| {
+        let ui = ui_handle.unwrap();
+        // Keys the CO₂ input did not handle itself are still typed into it, so they are not shortcuts.
+        if ui.get_editing_text() && e.text != "\u{1b}" {
+            return EventResult::Reject;
+        }
+
+        // Escape closes the cheat sheet before it hides the window.
+        let shortcut = Shortcut::from_event(&e);
+        if ui.get_show_shortcuts() && matches!(shortcut, Some(Shortcut::Help | Shortcut::Hide)) {
+            ui.set_show_shortcuts(false);
+            return EventResult::Accept;
+        }
+
+        let Some(shortcut) = shortcut else {
+            return EventResult::Reject;
+        };
+        match shortcut {
+            Shortcut::Help => {
+                ui.set_show_shortcuts(true);
+                EventResult::Accept
+            },
+            Shortcut::ScaleUp => {
+                step_ui_scale(&ui, UI_SCALE_STEP);
+                EventResult::Accept
+            },
+            Shortcut::ScaleDown => {
+                step_ui_scale(&ui, -UI_SCALE_STEP);
+                EventResult::Accept
+            },
+            Shortcut::Undo => {
+                undo::undo(&ui, &api);
+                EventResult::Accept
+            },
+            Shortcut::Hide => {
+                hide_window(&ui);
+                EventResult::Accept
+            },
+            Shortcut::Force => {
+                modify_config(&ui, &api, |cfg: &mut ThermostatConfig| {
+                    cfg.force = !cfg.force;
+                });
+                EventResult::Accept
+            },
+            Shortcut::MasterSwitch => {
+                toggle_master_switch(&ui, &api);
+                EventResult::Accept
+            },
+            Shortcut::RequireCo2 => {
+                // Toggle requiring CO₂, the target comes back as it was.
+                let endpoint = thermostats::selected();
+                modify_config(&ui, &api, |cfg: &mut ThermostatConfig| {
+                    cfg.co2_target = match cfg.co2_target {
+                        Some(co2_target) => {
+                            thermostats::remember_co2_target(&ui, endpoint, co2_target);
+                            None
+                        },
+                        None => Some(thermostats::last_co2_target(&ui, endpoint)),
+                    };
+                });
+                EventResult::Accept
+            },
+            Shortcut::Co2Preset => {
+                cycle_co2_preset(&ui, &api, &co2_presets);
+                EventResult::Accept
+            },
+            Shortcut::ClickThrough => {
+                let singletons = ui.global::<Singletons>();
+                let mut options = singletons.get_options();
+                options.click_through = !options.click_through;
+                singletons.set_options(options.clone());
+                if apply_click_through(&ui) {
+                    toast::show_info(&ui, &ui.global::<Texts>().invoke_click_through(options.click_through));
+                }
+                EventResult::Accept
+            },
+            Shortcut::Hold => {
+                hold::toggle(&ui, &api);
+                EventResult::Accept
+            },
+            Shortcut::Boost => {
+                boost::boost(&ui, &api);
+                EventResult::Accept
+            },
+            Shortcut::EditOptions => {
+                // Open the options file for the settings that have no key.
+                if !options_path.exists() {
+                    toast::show_warning(&ui, &ui.global::<Texts>().invoke_no_options_file());
+                } else if let Err(err) = open_with_default_app(&options_path) {
+                    error!("Could not open options file {:?}: {:?}", options_path, err);
+                    toast::show_warning(&ui, &ui.global::<Texts>().invoke_could_not_open_options());
+                } else {
+                    info!("Opened options file {:?}", options_path);
+                }
+                EventResult::Accept
+            },
+            Shortcut::CopyState => {
+                clipboard::copy_state(&ui);
+                EventResult::Accept
+            },
+            Shortcut::History => {
+                ui.set_show_history(!ui.get_show_history());
+                EventResult::Accept
+            },
+            Shortcut::Latency => {
+                latency::update_overlay(&ui);
+                ui.set_show_latency(!ui.get_show_latency());
+                EventResult::Accept
+            },
+            Shortcut::Compact => {
+                // Switch between the compact and the detailed layout, the window resizes to fit.
+                let singletons = ui.global::<Singletons>();
+                let mut options = singletons.get_options();
+                options.compact = !options.compact;
+                singletons.set_options(options);
+                EventResult::Accept
+            },
+            Shortcut::OnTop => {
+                // Toggle always on top, the window follows the option right away.
+                let singletons = ui.global::<Singletons>();
+                let mut options = singletons.get_options();
+                options.on_top = !options.on_top;
+                singletons.set_options(options.clone());
+                toast::show_info(&ui, &ui.global::<Texts>().invoke_on_top(options.on_top));
+                EventResult::Accept
+            },
+            Shortcut::Refresh => {
+                // Refresh right away, including the config in case it was changed on the device itself.
+                // The poller keeps its own schedule.
+                fetch_full_state(&ui, api.clone(), thermostats::selected());
+                EventResult::Accept
+            },
+            Shortcut::Theme => {
+                // Cycle through the system, dark and light themes.
+                let singletons = ui.global::<Singletons>();
+                let mut options = singletons.get_options();
+                options.theme = match options.theme {
+                    Theme::System => Theme::Dark,
+                    Theme::Dark => Theme::Light,
+                    Theme::Light => Theme::HighContrast,
+                    Theme::HighContrast => Theme::System,
+                };
+                singletons.set_options(options);
+                apply_ui_scale(&ui); // High contrast is larger.
+                EventResult::Accept
+            },
+            Shortcut::TemperatureUnit => {
+                // Switch between Celsius and Fahrenheit.
+                let singletons = ui.global::<Singletons>();
+                let mut options = singletons.get_options();
+                options.temperature_unit = match options.temperature_unit {
+                    TemperatureUnit::Celsius => TemperatureUnit::Fahrenheit,
+                    TemperatureUnit::Fahrenheit => TemperatureUnit::Celsius,
+                };
+                singletons.set_options(options);
+                EventResult::Accept
+            },
+            Shortcut::ResetTarget => {
+                let target_temp = ui.global::<Singletons>().get_options().default_target_temp;
+                modify_config(&ui, &api, |cfg: &mut ThermostatConfig| {
+                    cfg.target_temp = target_temp;
+                });
+                EventResult::Accept
+            },
+            Shortcut::TargetUp => {
+                let step = ui.global::<Functions>().invoke_temperature_step();
+                modify_config(&ui, &api, |cfg: &mut ThermostatConfig| {
+                    cfg.target_temp += step;
+                });
+                EventResult::Accept
+            },
+            Shortcut::TargetDown => {
+                let step = ui.global::<Functions>().invoke_temperature_step();
+                modify_config(&ui, &api, |cfg: &mut ThermostatConfig| {
+                    cfg.target_temp -= step;
+                });
+                EventResult::Accept
+            },
+        }
+    });
+}
+
+/// Sets the CO₂ target to the preset after the current one, and stops requiring CO₂ after the last preset.
+fn cycle_co2_preset(ui: &AppWindow, api: &Api, presets: &[i32]) {
+    if ui.global::<Singletons>().get_options().read_only {
+        return;
+    }
+    if presets.is_empty() {
+        toast::show_info(ui, &ui.global::<Texts>().invoke_no_co2_presets());
+        return;
+    }
+
+    let cfg: ThermostatConfig = ui.global::<Singletons>().get_config().into();
+    let next = match cfg.co2_target {
+        None => presets.first().copied(),
+        Some(co2_target) => match presets.iter().position(|&preset| preset == co2_target) {
+            Some(i) => presets.get(i + 1).copied(),
+            // Not a preset, go on with the next higher one.
+            None => presets.iter().copied().find(|&preset| preset > co2_target).or(presets.first().copied()),
+        },
+    };
+
+    modify_config(ui, api, |cfg: &mut ThermostatConfig| cfg.co2_target = next);
+    match next {
+        Some(co2_target) => toast::show_info(ui, &ui.global::<Texts>().invoke_co2_target(co2_target)),
+        None => toast::show_info(ui, &ui.global::<Texts>().invoke_co2_no_longer_required()),
+    }
+}
+
+/// Opens `path` the way the OS would if it were double-clicked.
+fn open_with_default_app(path: &Path) -> std::io::Result<()> {
+    #[cfg(windows)]
+    let mut command = std::process::Command::new("explorer"); // Unlike start, does not flash a console window.
+    #[cfg(target_os = "macos")]
+    let mut command = std::process::Command::new("open");
+    #[cfg(not(any(windows, target_os = "macos")))]
+    let mut command = std::process::Command::new("xdg-open");
+
+    command.arg(path).spawn()?;
+    Ok(())
+}
+
+/// Scales the whole UI by `ui_scale` on top of the scale factor of the monitor, and a bit more for high contrast.
+fn apply_ui_scale(ui: &AppWindow) {
+    let native = NATIVE_SCALE_FACTOR.get().unwrap_or_else(|| {
+        let native = ui.window().scale_factor();
+        NATIVE_SCALE_FACTOR.set(Some(native));
+        native
+    });
+
+    let options = ui.global::<Singletons>().get_options();
+    let theme_scale = if options.theme == Theme::HighContrast { HIGH_CONTRAST_SCALE } else { 1.0 };
+    let scale_factor = native * options.ui_scale * theme_scale;
+    ui.window().dispatch_event(slint::platform::WindowEvent::ScaleFactorChanged { scale_factor });
+}
+
+fn step_ui_scale(ui: &AppWindow, step: f32) {
+    let singletons = ui.global::<Singletons>();
+    let mut options = singletons.get_options();
+    // Round, so stepping back and forth does not accumulate float errors.
+    let ui_scale = ((options.ui_scale + step) * 10.0).round() / 10.0;
+    options.ui_scale = ui_scale.clamp(MIN_UI_SCALE, MAX_UI_SCALE);
+    singletons.set_options(options.clone());
+
+    apply_ui_scale(ui);
+    toast::show_info(ui, &ui.global::<Texts>().invoke_scale((options.ui_scale * 100.0).round() as i32));
+}
+
+fn register_focus_handler(ui: &AppWindow) {
+    let ui_handle = ui.as_weak();
+    ui.on_focus_change(move |has_focus| {
+        let ui = ui_handle.unwrap();
+        let options = ui.global::<Singletons>().get_options();
+        ui.set_window_opacity(if has_focus { options.opacity_focused } else { options.opacity_unfocused });
+    });
+}
+
+fn register_thermostat_handler(ui: &AppWindow, api: Api) {
+    let ui_handle = ui.as_weak();
+    ui.on_request_thermostat_change(move |endpoint: i32| {
+        let ui = ui_handle.unwrap();
+        thermostats::select(&ui, &api, endpoint as usize);
+    });
+}
+
+fn register_retry_handler(ui: &AppWindow, api: Api) {
+    let ui_handle = ui.as_weak();
+    ui.on_request_retry(move || {
+        let ui = ui_handle.unwrap();
+        fetch_full_state(&ui, api.clone(), thermostats::selected());
+    });
+}
+
+fn register_splash_timeout_handler(ui: &AppWindow, api: Api) {
+    let ui_handle = ui.as_weak();
+    ui.on_splash_timed_out(move || {
+        let ui = ui_handle.unwrap();
+        let endpoint = thermostats::selected();
+        warn!("{} did not respond in time, hiding the splash anyway", api.endpoints[endpoint].name);
+
+        // Offer a retry right away instead of waiting for the first request to give up.
+        if thermostats::confirmed_config(endpoint).is_none() {
+            ui.global::<Singletons>().set_unreachable_url(api.url(endpoint).into());
+        }
+    });
+}
+
+fn register_latency_handler(ui: &AppWindow) {
+    let ui_handle = ui.as_weak();
+    ui.on_request_latency_refresh(move || {
+        latency::update_overlay(&ui_handle.unwrap());
+    });
+}
+
+fn register_freshness_handler(ui: &AppWindow) {
+    let ui_handle = ui.as_weak();
+    ui.on_request_freshness_refresh(move || {
+        thermostats::update_freshness(&ui_handle.unwrap());
+    });
+}
+
+/// Gets the config and state of a thermostat from the API asynchronously,
+/// done on startup, on a manual refresh and whenever another thermostat is selected.
+fn fetch_full_state(ui: &AppWindow, api: Api, endpoint: usize) {
+    let ui_handle = ui.as_weak();
+    tokio::spawn(async move {
+        let resp = get_api_async(&api, endpoint, true).await;
+        if let Ok(resp) = &resp {
+            clock::record(endpoint, resp.time);
+        }
+
+        let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+            match resp {
+                Ok(resp) => {
+                    if !resp.success {
+                        let msg = resp.error.unwrap();
+                        error!(api_error = msg.as_str(); "API returned an error");
+                        set_connection(ui.as_weak(), endpoint, ConnectionStatus::Rejected);
+                        toast::show_error(ui.as_weak(), endpoint, move |texts| texts.invoke_thermostat_error(msg.into()));
+                        return;
+                    }
+
+                    let data = resp.data.unwrap();
+                    let Some(cfg) = data.config else {
+                        // Only the state can be shown, the controls would send a config the server does not understand.
+                        error!("API response has no config that can be read");
+                        thermostats::apply_endpoint_state(&ui, endpoint, data.state);
+                        ui.invoke_hide_splash();
+                        return;
+                    };
+                    thermostats::confirm_config(&ui, endpoint, cfg);
+                    if thermostats::is_selected(endpoint) {
+                        ui.global::<Singletons>().set_config(cfg.into());
+                    }
+                    frost::check(&ui, &api, endpoint, &data.state);
+                    thermostats::apply_endpoint_state(&ui, endpoint, data.state);
+
+                    // Hide the splash window.
+                    ui.invoke_hide_splash();
+                },
+                Err(err) => {
+                    log_request_error("Could not get config from API", &err);
+                    set_connection(ui.as_weak(), endpoint, (&err).into());
+
+                    // Never reached at all so far, e.g. on startup. Say so plainly instead of leaving the splash up.
+                    if thermostats::is_selected(endpoint) && thermostats::confirmed_config(endpoint).is_none() {
+                        if is_transport_error(&err) {
+                            ui.global::<Singletons>().set_unreachable_url(api.url(endpoint).into());
+                        }
+                        ui.invoke_hide_splash();
+                    }
+                    let failure = describe_request_error(&err);
+                    toast::show_error(ui.as_weak(), endpoint, move |texts| texts.invoke_could_not_get_config(failure));
+                },
+            }
+        });
+    });
+}
+
+fn start_ui_updater(ui: &AppWindow, api: Api) {
+    // Periodically update the UI with the latest data from the API.
+    // All thermostats are polled, so switching between them shows a recent state right away.
+    let ui_handle = ui.as_weak();
+    tokio::spawn(async move {
+        let mut interval = poll_interval(POLL_INTERVAL_SECS.load(Ordering::SeqCst));
+
+        loop {
+            interval.tick().await; // Run every poll interval
+
+            // Nobody is looking, so wait until the window is shown again and refresh right away.
+            let shown = WINDOW_SHOWN_NOTIFY.notified(); // Created before checking, so a show in between is not missed.
+            if !WINDOW_SHOWN.load(Ordering::SeqCst) {
+                shown.await;
+                interval.reset();
+            }
+
+            let requests = (0..api.endpoints.len()).map(|endpoint| get_api_async(&api, endpoint, false));
+            for (endpoint, result) in join_all(requests).await.into_iter().enumerate() {
+                match result {
+                    Ok(resp) => {
+                        let reachable = resp.success;
+                        try_apply_response(ui_handle.clone(), &api, endpoint, resp);
+                        if reachable {
+                            replay_queued_update(ui_handle.clone(), api.clone(), endpoint);
+                        }
+                    },
+                    Err(err) => {
+                        log_request_error(&format!("Could not get metrics of {} from API", api.endpoints[endpoint].name), &err);
+                        set_connection(ui_handle.clone(), endpoint, (&err).into());
+                        let failure = describe_request_error(&err);
+                        toast::show_error(ui_handle.clone(), endpoint, move |texts| texts.invoke_could_not_refresh(failure));
+                    },
+                }
+            }
+        }
+    });
+}
+
+fn poll_interval(secs: u64) -> Interval {
+    let period = Duration::from_secs(secs);
+    tokio::time::interval_at(Instant::now() + period, period)
+}
+
+fn start_live_feed(ui: &AppWindow, api: Api, endpoint: usize) {
+    // Apply state pushes from the server the moment they arrive.
+    // Polling keeps running alongside as a safety net in case the socket silently dies.
+    if api.mock.is_some() {
+        return; // The mock only answers requests.
+    }
+    let api_url = api.url(endpoint);
+    let Some(url) = live_feed_url(api_url) else {
+        warn!("Could not derive live feed URL from {}, relying on polling only", api_url);
+        return;
+    };
+    let mut request = match url.as_str().into_client_request() {
+        Ok(request) => request,
+        Err(err) => {
+            warn!("Could not create live feed request for {}, relying on polling only: {}", url, err);
+            return;
+        },
+    };
+    if let Some(auth) = &api.auth {
+        request.headers_mut().insert(AUTHORIZATION, auth.clone());
+    }
+
+    let ui_handle = ui.as_weak();
+    tokio::spawn(async move {
+        const RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+        loop {
+            match tokio::time::timeout(api.timeout, tokio_tungstenite::connect_async(request.clone())).await {
+                Ok(Ok((mut socket, _))) => {
+                    info!("Connected to live feed at {}", url);
+
+                    while let Some(msg) = socket.next().await {
+                        match msg {
+                            Ok(Message::Text(text)) => match serde_json::from_str::<APIResponse>(text.as_str()) {
+                                Ok(resp) => try_apply_response(ui_handle.clone(), &api, endpoint, resp),
+                                Err(err) => error!("Could not parse live feed message: {:?}", err),
+                            },
+                            Ok(Message::Close(_)) => break,
+                            Ok(_) => {}, // Pings are answered by tungstenite itself.
+                            Err(err) => {
+                                error!("Live feed error: {:?}", err);
+                                break;
+                            },
+                        }
+                    }
+
+                    warn!("Live feed disconnected, falling back to polling");
+                },
+                Ok(Err(err)) => info!("Could not connect to live feed, relying on polling: {}", err),
+                Err(_) => info!("Connecting to live feed timed out, relying on polling"),
+            }
+
+            sleep(RECONNECT_DELAY).await;
+        }
+    });
+}
+
+/// Derives the WebSocket URL of the live feed from the API URL,
+/// e.g. `http://host:5567/` becomes `ws://host:5567/ws`.
+fn live_feed_url(api_url: &str) -> Option<reqwest::Url> {
+    let mut url = reqwest::Url::parse(api_url).ok()?;
+    let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+    url.set_scheme(scheme).ok()?;
+    url.join("ws").ok()
+}
+
+/// Writes the options to disk in JSON format.
+fn save_options(options: &Options, path: &Path) -> Result<()> {
+    write_json(options, path)
+}
+
+/// Writes `value` to `path` as pretty JSON, see `write_atomically`.
+fn write_json(value: &impl serde::Serialize, path: &Path) -> Result<()> {
+    write_atomically(path, |writer| Ok(serde_json::to_writer_pretty(writer, value)?))
+}
+
+/// Has `write` write to a temporary file next to `path` first and only then replaces `path` with it,
+/// so a crash halfway never leaves a half-written file behind.
+fn write_atomically(path: &Path, write: impl FnOnce(&mut BufWriter<fs::File>) -> Result<()>) -> Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let mut writer = BufWriter::new(fs::File::create(&tmp_path)?);
+    write(&mut writer)?;
+    writer.into_inner().map_err(|err| err.into_error())?.sync_all()?; // On disk before it replaces anything.
+
+    fs::rename(&tmp_path, path).with_context(|| format!("could not replace {:?}", path))?;
     Ok(())
 }
 
-fn register_target_temp_handler(ui: &AppWindow) {
-    let ui_handle = ui.as_weak();
-    let mut task: Option<JoinHandle<()>> = None;
-    let mut last: Instant = Instant::now();
-    const UPDATE_MARGIN: Duration = Duration::from_millis(250);
-
-    ui.on_request_config_change(move || {
-        let ui_handle = ui_handle.clone();
+/// Reads the config and state cached by the last session, if any.
+fn load_cached_state(path: &Path) -> Option<CachedState> {
+    let contents = fs::read_to_string(path).ok()?;
+
+    serde_json::from_str(&contents)
+        .map_err(|err| error!("Could not read cached state from disk: {:?}", err))
+        .ok()
+}
+
+/// Toggles the master switch, asking for confirmation first when turning it off if enabled.
+fn toggle_master_switch(ui: &AppWindow, api: &Api) {
+    let singletons = ui.global::<Singletons>();
+    if singletons.get_options().read_only {
+        return;
+    }
+
+    if singletons.get_config().master_switch && singletons.get_options().confirm_master_off {
+        singletons.set_confirming_master_off(true); // The UI sends the update once confirmed.
+        return;
+    }
+
+    modify_config(ui, api, |cfg: &mut ThermostatConfig| {
+        cfg.master_switch = !cfg.master_switch;
+    });
+}
+
+/// Undoes toggles of force or the master switch that come too soon after the previous one.
+/// Returns whether there is anything left to send.
+fn enforce_toggle_cooldown(ui: &AppWindow) -> bool {
+    let singletons = ui.global::<Singletons>();
+    let cooldown = Duration::from_millis(singletons.get_options().toggle_cooldown_ms as u64);
+    let endpoint = thermostats::selected();
+    let mut cfg = singletons.get_config();
+
+    let (undone, changed) = CONFIG_UPDATES.with_borrow_mut(|updates| {
+        let previous = updates.toggles.previous.clone();
+        let undone = updates.toggles.apply(endpoint, &mut cfg, cooldown);
+        (undone, previous.is_none_or(|(_, previous)| previous != cfg))
+    });
+    if !undone {
+        return true;
+    }
+
+    info!("Ignored a toggle within {:?} of the previous one", cooldown);
+    singletons.set_config(cfg);
+    toast::show_info(ui, &ui.global::<Texts>().invoke_wait_before_switching());
+    changed
+}
+
+/// Sends the config shown in the UI to the API, coalescing rapid changes into a single update.
+/// If the last change was less than the debounce margin ago, the update is scheduled for later,
+/// replacing any update scheduled before. Otherwise, it is sent immediately.
+fn queue_config_update(ui: &AppWindow, api: &Api) {
+    if !enforce_toggle_cooldown(ui) {
+        return;
+    }
+
+    // While CO₂ is required, that is the target to come back to. Turning it off in the UI leaves the target in the config too.
+    let co2_target = ui.global::<Singletons>().get_config().co2_target;
+    if (MIN_CO2_TARGET..=MAX_CO2_TARGET).contains(&co2_target) {
+        thermostats::remember_co2_target(ui, thermostats::selected(), co2_target);
+    }
+
+    // The UI already shows the change, a failure of an earlier update must not roll it back.
+    CONFIG_GENERATION.fetch_add(1, Ordering::SeqCst);
+    discard_queued_update(ui);
+    // Force on by frost protection is not timed out, it lets go by itself once the room has warmed up.
+    force::update(ui, api, ui.global::<Singletons>().get_config().force && !frost::is_engaged(thermostats::selected()));
+
+    let update_margin = Duration::from_millis(ui.global::<Singletons>().get_options().debounce_ms as u64);
+    let delay = CONFIG_UPDATES.with_borrow_mut(|updates| {
+        let do_delay = updates.last.is_some_and(|last| last.elapsed() < update_margin);
+        updates.last = Some(Instant::now());
+        if do_delay { update_margin } else { Duration::ZERO } // Wait for the user to stop modifying.
+    });
+    schedule_config_update(ui, api, delay);
+}
+
+/// Sends the config shown in the UI after `delay`, or once the rate limit allows if that is later.
+/// The config is only picked up when it is sent, so a throttled update always sends the latest one.
+fn schedule_config_update(ui: &AppWindow, api: &Api, delay: Duration) {
+    let per_minute = ui.global::<Singletons>().get_options().max_updates_per_minute;
+    let ui_handle = ui.as_weak();
+    let api = api.clone();
+
+    let throttled = CONFIG_UPDATES.with_borrow_mut(|updates| {
+        // If there is already an update scheduled, cancel it.
+        if let Some(jh) = updates.scheduled.take() {
+            jh.abort();
+        }
+
+        let throttle = updates.limiter.wait(per_minute);
+        let delay = delay.max(throttle);
+
+        // Spawn a new task that will update the config after the delay.
+        updates.scheduled = Some(tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+
+            let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+                // Another update may have used up the token in the meantime, e.g. a replay.
+                let throttle = CONFIG_UPDATES.with_borrow_mut(|updates| updates.limiter.wait(per_minute));
+                if !throttle.is_zero() {
+                    schedule_config_update(&ui, &api, throttle);
+                    return;
+                }
+
+                ui.global::<Singletons>().set_throttled(false);
+                let cfg = ui.global::<Singletons>().get_config().into();
+                update_config(&ui, &api, thermostats::selected(), cfg);
+            });
+        }));
+        !throttle.is_zero()
+    });
+
+    if throttled {
+        info!("Config update throttled to {} per minute", per_minute);
+        ui.global::<Singletons>().set_throttled(true);
+    }
+}
+
+/// Sends a config the app has to set by itself right away, e.g. for frost protection. Unlike a change by the user,
+/// it skips the debounce and the rate limit, and does not count toward the toggle cooldown, the force timeout or the undo history.
+fn set_config_now(ui: &AppWindow, api: &Api, endpoint: usize, cfg: ThermostatConfig) {
+    if thermostats::is_selected(endpoint) {
+        if CONFIG_UPDATES.with_borrow(|updates| updates.queued.is_some_and(|queued| queued.endpoint == endpoint)) {
+            discard_queued_update(ui);
+        }
+        ui.global::<Singletons>().set_config(cfg.into());
+        // The next change by the user is compared against this, so it does not count as toggling back.
+        CONFIG_UPDATES.with_borrow_mut(|updates| updates.toggles.previous = Some((endpoint, cfg.into())));
+    }
+    update_config(ui, api, endpoint, cfg);
+}
+
+/// Sends a config the user went back to, e.g. by undoing a change. Switching back is what they asked for,
+/// so the toggle cooldown does not hold it back.
+fn restore_config(ui: &AppWindow, api: &Api, mut cfg: ThermostatConfig) {
+    let singletons = ui.global::<Singletons>();
+    let options = singletons.get_options();
+    cfg.target_temp = cfg.target_temp.clamp(options.min_target_temp, options.max_target_temp);
+
+    singletons.set_config(cfg.into());
+    CONFIG_UPDATES.with_borrow_mut(|updates| updates.toggles.previous = Some((thermostats::selected(), cfg.into())));
+    queue_config_update(ui, api);
+}
+
+/// Modify the thermostat config on behalf of the user, so the change can be undone.
+fn modify_config(ui: &AppWindow, api: &Api, f: impl FnOnce(&mut ThermostatConfig)) {
+    change_config(ui, api, f, true);
+}
+
+/// Modify the thermostat config for a change the app makes by itself, e.g. on schedule, which is kept out of the undo history.
+fn modify_config_automatically(ui: &AppWindow, api: &Api, f: impl FnOnce(&mut ThermostatConfig)) {
+    change_config(ui, api, f, false);
+}
+
+fn change_config(ui: &AppWindow, api: &Api, f: impl FnOnce(&mut ThermostatConfig), undoable: bool) {
+    let singletons = ui.global::<Singletons>(); // Get the Singletons module.
+    if singletons.get_options().read_only {
+        return;
+    }
+
+    let mut cfg: ThermostatConfig = singletons.get_config().into(); // Get config.
+    let old_cfg = cfg;
+    f(&mut cfg); // Modify config.
+
+    // Never send an unsafe target to the hardware.
+    let options = singletons.get_options();
+    cfg.target_temp = cfg.target_temp.clamp(options.min_target_temp, options.max_target_temp);
+    if cfg == old_cfg {
+        return; // Nothing changed, e.g. because the target was already at its limit.
+    }
+
+    if undoable {
+        undo::record(cfg);
+    }
+    singletons.set_config(cfg.into()); // Set config.
+
+    queue_config_update(ui, api);
+}
+
+// Sends a PATCH request to the API to update the config.
+// This is done asynchronously. A request still in flight is canceled, as this one overwrites its result anyway.
+fn update_config(ui: &AppWindow, api: &Api, endpoint: usize, cfg: ThermostatConfig) {
+    // The UI clamps everything already, this only catches what slips through.
+    if let Err(err) = cfg.validate(&ui.global::<Singletons>().get_options()) {
+        error!("Not sending invalid config {:?}: {}", cfg, err);
+        toast::show_warning(ui, &ui.global::<Texts>().invoke_change_not_sent());
+        if let Some(confirmed) = thermostats::confirmed_config(endpoint).filter(|_| thermostats::is_selected(endpoint)) {
+            ui.global::<Singletons>().set_config(confirmed.into());
+        }
+        return;
+    }
+
+    let ui_handle = ui.as_weak();
+    let api = api.clone();
+    let base = thermostats::confirmed_config(endpoint);
+    let generation = CONFIG_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    let jh = tokio::spawn(async move {
+        // Send PATCH request to API
+        match patch_api_async(&api, endpoint, cfg, base, generation).await {
+            Ok(resp) => {
+                let accepted = resp.success;
+                let echoed = resp.data.as_ref().and_then(|data| data.config); // Not every server sends it back.
+                try_apply_response(ui_handle.clone(), &api, endpoint, resp);
+                settle_config(ui_handle, endpoint, cfg, echoed, generation, accepted);
+            },
+            Err(_) if CONFIG_GENERATION.load(Ordering::SeqCst) != generation => {
+                info!("Dropped failed config update {:?} as it was superseded by a newer one", cfg);
+            },
+            Err(err) if !is_transport_error(&err) => {
+                log_request_error("Error sending API request", &err);
+                set_connection(ui_handle.clone(), endpoint, (&err).into());
+                let failure = describe_request_error(&err);
+                toast::show_error(ui_handle.clone(), endpoint, move |texts| texts.invoke_could_not_update(failure));
+                settle_config(ui_handle, endpoint, cfg, None, generation, false);
+            },
+            Err(err) => {
+                log_request_error("Error sending API request", &err);
+                set_connection(ui_handle.clone(), endpoint, (&err).into());
+                let failure = describe_request_error(&err);
+                toast::show_error(ui_handle.clone(), endpoint, move |texts| texts.invoke_could_not_update_queued(failure));
+                queue_for_replay(ui_handle, endpoint, cfg, generation);
+            },
+        }
+    });
+
+    let per_minute = ui.global::<Singletons>().get_options().max_updates_per_minute;
+    CONFIG_UPDATES.with_borrow_mut(|updates| {
+        updates.limiter.take(per_minute);
+        if let Some((_, previous)) = updates.in_flight.replace((cfg, jh)) {
+            previous.abort(); // No-op if it already finished.
+        }
+    });
+}
+
+/// Remembers an accepted config, or rolls the UI back to the last accepted one if the update failed.
+/// If the API sent back the config it ended up with, the user is told whether it matches what was sent.
+/// Rollbacks and feedback are skipped if the config was changed again since, so newer changes are not clobbered.
+fn settle_config(ui_handle: Weak<AppWindow>, endpoint: usize, cfg: ThermostatConfig, echoed: Option<ThermostatConfig>, generation: u64, accepted: bool) {
+    let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+        let is_latest = CONFIG_GENERATION.load(Ordering::SeqCst) == generation && thermostats::is_selected(endpoint);
+
+        if accepted {
+            thermostats::confirm_config(&ui, endpoint, echoed.unwrap_or(cfg));
+            match echoed {
+                Some(echoed) if is_latest && echoed != cfg => {
+                    info!("API adjusted config update {:?} to {:?}", cfg, echoed);
+                    ui.global::<Singletons>().set_config(echoed.into());
+                    toast::show_warning(&ui, &describe_adjustment(&ui, cfg, echoed));
+                },
+                Some(_) if is_latest => toast::show_info(&ui, &ui.global::<Texts>().invoke_change_saved()),
+                _ => {},
+            }
+            return;
+        }
+
+        if !is_latest {
+            return;
+        }
+
+        if let Some(confirmed) = thermostats::confirmed_config(endpoint) {
+            info!("Rolling back config to {:?} after update {:?} failed", confirmed, cfg);
+            ui.global::<Singletons>().set_config(confirmed.into());
+        }
+    });
+}
+
+/// Keeps a config update that could not reach the API, so it is sent again once the API responds.
+/// The UI keeps showing the change in the meantime.
+fn queue_for_replay(ui_handle: Weak<AppWindow>, endpoint: usize, cfg: ThermostatConfig, generation: u64) {
+    let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+        if CONFIG_GENERATION.load(Ordering::SeqCst) != generation {
+            return; // A newer change is on its way already.
+        }
+
+        info!("Queued config update {:?} until the API can be reached again", cfg);
+        let base = thermostats::confirmed_config(endpoint);
+        CONFIG_UPDATES.with_borrow_mut(|updates| updates.queued = Some(QueuedUpdate { endpoint, cfg, base }));
+        ui.global::<Singletons>().set_pending_sync(true);
+    });
+}
+
+/// Drops the queued config update, as a newer change replaces it.
+fn discard_queued_update(ui: &AppWindow) {
+    if let Some(queued) = CONFIG_UPDATES.with_borrow_mut(|updates| updates.queued.take()) {
+        info!("Discarded queued config update {:?} in favor of a newer change", queued.cfg);
+    }
+    ui.global::<Singletons>().set_pending_sync(false); // Also covers a replay whose check is still in flight.
+}
+
+/// Sends the queued config update again now that `endpoint` responded,
+/// unless the config of the thermostat was changed elsewhere in the meantime.
+fn replay_queued_update(ui_handle: Weak<AppWindow>, api: Api, endpoint: usize) {
+    let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+        let Some(queued) = CONFIG_UPDATES.with_borrow_mut(|updates| updates.queued.take_if(|queued| queued.endpoint == endpoint)) else { return };
+        let generation = CONFIG_GENERATION.load(Ordering::SeqCst);
+
+        let ui_handle = ui.as_weak();
+        tokio::spawn(async move {
+            // Replaying over a change made elsewhere would silently undo it, so check the current config first.
+            let resp = get_api_async(&api, endpoint, true).await;
+            if let Ok(resp) = &resp {
+                clock::record(endpoint, resp.time);
+            }
+            let current = resp.ok().filter(|resp| resp.success).and_then(|resp| resp.data).and_then(|data| data.config);
+
+            let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+                if CONFIG_GENERATION.load(Ordering::SeqCst) != generation {
+                    return; // Superseded by a newer change.
+                }
+
+                let singletons = ui.global::<Singletons>();
+                match current {
+                    None => CONFIG_UPDATES.with_borrow_mut(|updates| updates.queued = Some(queued)), // Gone again, wait for the next poll.
+                    Some(current) if queued.base.is_some_and(|base| base != current) => {
+                        info!("Discarded queued config update {:?}, as the config was changed to {:?} elsewhere", queued.cfg, current);
+                        singletons.set_pending_sync(false);
+                        thermostats::confirm_config(&ui, endpoint, current);
+                        if thermostats::is_selected(endpoint) {
+                            singletons.set_config(current.into());
+                        }
+                        toast::show_warning(&ui, &ui.global::<Texts>().invoke_change_discarded());
+                    },
+                    Some(_) => {
+                        info!("Sending queued config update {:?}", queued.cfg);
+                        singletons.set_pending_sync(false);
+                        update_config(&ui, &api, endpoint, queued.cfg);
+                    },
+                }
+            });
+        });
+    });
+}
+
+/// Tells the user what the API changed about a config update, e.g. a clamped target temperature.
+fn describe_adjustment(ui: &AppWindow, sent: ThermostatConfig, echoed: ThermostatConfig) -> SharedString {
+    let texts = ui.global::<Texts>();
+    if echoed.target_temp != sent.target_temp {
+        return texts.invoke_target_adjusted(ui.global::<Functions>().invoke_format_temp(echoed.target_temp));
+    }
+
+    match (sent.co2_target, echoed.co2_target) {
+        (Some(sent), Some(echoed)) if sent != echoed => texts.invoke_co2_target_adjusted(echoed),
+        (Some(_), None) => texts.invoke_co2_not_accepted(),
+        _ => texts.invoke_change_not_accepted(),
+    }
+}
+
+fn try_apply_response(ui_handle: Weak<AppWindow>, api: &Api, endpoint: usize, resp: APIResponse) {
+    clock::record(endpoint, resp.time);
+    if resp.success {
+        // Ignore result, we don't care if it actually updated.
+        // If it didn't, the UI is probably gone anyway.
+        let api = api.clone();
+        let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+            let state = resp.data.unwrap().state;
+            frost::check(&ui, &api, endpoint, &state);
+            thermostats::apply_endpoint_state(&ui, endpoint, state);
+        });
+    } else {
+        let msg = resp.error.unwrap();
+        error!(api_error = msg.as_str(); "API returned an error");
+        set_connection(ui_handle.clone(), endpoint, ConnectionStatus::Rejected);
+        toast::show_error(ui_handle, endpoint, move |texts| texts.invoke_thermostat_error(msg.into()));
+    }
+}
+
+/// Shows a fresh state received from the API.
+fn apply_state(ui: &AppWindow, state: APIResponseStateData) {
+    // Readings of an offline sensor are meaningless, keep them out of the graph and alerts.
+    if state.available {
+        history::record(ui, state.temperature);
+        notifications::check_target_reached(ui, state.temperature, state.is_heating);
+        notifications::check_co2(ui, state.co2);
+    }
 
-        // If there is already a task running, cancel it.
-        if let Some(jh) = &task {
-            if !jh.is_finished() {
-                jh.abort();
-            }
-        }
+    let singletons = ui.global::<Singletons>();
+    singletons.set_state(state.into());
+    singletons.set_connection(ConnectionStatus::Connected);
+    singletons.set_unreachable_url(Default::default());
+    toast::clear(ui);
+    singletons.set_stale(false);
 
-        // If the last update was less than 250ms ago, schedule an update for later.
-        // Otherwise, update immediately.
-        let do_delay = last.elapsed() < UPDATE_MARGIN;
-        last = Instant::now();
+    #[cfg(windows)]
+    tray::update_tooltip(ui);
+}
 
-        // Spawn a new task that will update the config after 250ms.
-        let jh = tokio::spawn(async move {
-            if do_delay {
-                tokio::time::sleep(UPDATE_MARGIN).await; // Wait for the user to stop modifying.
-            }
+/// Updates the connection indicator if `endpoint` is the selected thermostat, can be called from any thread.
+fn set_connection(ui_handle: Weak<AppWindow>, endpoint: usize, status: ConnectionStatus) {
+    if !thermostats::is_selected(endpoint) {
+        return;
+    }
 
-            let _ = ui_handle.upgrade_in_event_loop(move |ui| {
-                let cfg = ui.global::<Singletons>().get_config().into();
-                update_config(&ui, cfg);
-            });
-        });
+    let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+        ui.global::<Singletons>().set_connection(status);
+    });
+}
 
-        if do_delay {
-            task = Some(jh);
+impl From<&reqwest::Error> for ConnectionStatus {
+    fn from(err: &reqwest::Error) -> Self {
+        if is_unauthorized(err) {
+            Self::Unauthorized
+        } else if is_transport_error(err) {
+            Self::Disconnected
+        } else {
+            Self::Rejected
         }
-    });
+    }
 }
 
-fn register_window_move_handler(ui: &AppWindow) {
-    let ui_handle = ui.as_weak();
-    ui.on_request_window_move(move |dx: i32, dy: i32| {
-        let ui = ui_handle.unwrap();
-        let pos = ui.window().position(); // Current position
+/// Send a PATCH request to the API, with only the fields that differ from `base`, the config the server last confirmed.
+/// Failed requests are only retried as long as no newer config update than `generation` has been sent.
+async fn patch_api_async(api: &Api, endpoint: usize, new_config: ThermostatConfig, base: Option<ThermostatConfig>, generation: u64) -> Result<APIResponse, reqwest::Error> {
+    let patch = ConfigPatch::new(new_config, base);
+    info!("Updating config of {} to {:?} with {:?}", api.endpoints[endpoint].name, new_config, patch);
+    if let Some(mock) = &api.mock {
+        return Ok(mock.patch(endpoint, new_config).await);
+    }
 
-        // Move the window along with the cursor.
-        ui.window().set_position(WindowPosition::Physical(PhysicalPosition { x: pos.x + dx, y: pos.y + dy }));
-    });
+    let patch = &patch;
+    with_retry(&api.retry, || CONFIG_GENERATION.load(Ordering::SeqCst) == generation, || async move {
+        let start = Instant::now();
+        let result = async {
+            api.client.patch(api.url(endpoint))
+                .json(patch)
+                .send()
+                .await
+                .and_then(check_unauthorized)?
+                .json::<APIResponse>()
+                .await
+        }.await;
+        latency::record("PATCH", &api.endpoints[endpoint].name, start.elapsed());
+        result
+    }).await
 }
 
-fn register_quit_handler(ui: &AppWindow) {
-    let ui_handle = ui.as_weak();
-    ui.on_request_quit(move || {
-        let ui = ui_handle.unwrap();
-        let _ = ui.window().hide(); // We do not care about the result here.
-    });
+/// Get the current thermostat config and states from the API.
+async fn get_api_async(api: &Api, endpoint: usize, include_config: bool) -> Result<APIResponse, reqwest::Error> {
+    if let Some(mock) = &api.mock {
+        return Ok(mock.get(endpoint, include_config).await);
+    }
+
+    with_retry(&api.retry, || true, || async move {
+        let start = Instant::now();
+        let result = async {
+            api.client.get(api.url(endpoint).to_owned() + "?include_config=" + &include_config.to_string())
+                .send()
+                .await
+                .and_then(check_unauthorized)?
+                .json()
+                .await
+        }.await;
+        latency::record("GET", &api.endpoints[endpoint].name, start.elapsed());
+        result
+    }).await
 }
 
-fn register_key_handler(ui: &AppWindow) {
-    let ui_handle = ui.as_weak();
-    ui.on_key_pressed(move |e: KeyEvent| {
-        let ui = ui_handle.unwrap();
-        match e.text.as_str() {
-            "\u{1b}" => { // Escape key
-                let _ = ui.window().hide(); // We do not care about the result here.
-                EventResult::Accept
-            },
-            "f" => {
-                modify_config(&ui, |cfg: &mut ThermostatConfig| {
-                    cfg.force = !cfg.force;
-                });
-                EventResult::Accept
-            },
-            "\u{f700}" => { // Up arrow
-                modify_config(&ui, |cfg: &mut ThermostatConfig| {
-                    cfg.target_temp += TEMPERATURE_STEP;
-                });
-                EventResult::Accept
-            },
-            "\u{f701}" => { // Down arrow
-                modify_config(&ui, |cfg: &mut ThermostatConfig| {
-                    cfg.target_temp -= TEMPERATURE_STEP;
-                });
-                EventResult::Accept
+/// Runs `request` until it succeeds or runs out of retries, doubling the delay between attempts.
+/// `may_retry` is checked after every delay and can cancel the remaining attempts.
+async fn with_retry<T, Fut>(retry: &RetryOptions, may_retry: impl Fn() -> bool, request: impl Fn() -> Fut) -> Result<T, reqwest::Error>
+where Fut: Future<Output = Result<T, reqwest::Error>> {
+    let mut backoff = Duration::from_millis(retry.initial_backoff_ms);
+    let mut attempt = 0;
+
+    loop {
+        match request().await {
+            Ok(res) => return Ok(res),
+            Err(err) if attempt < retry.max_retries && is_transport_error(&err) => {
+                attempt += 1;
+                let reason = if err.is_timeout() { "timed out" } else { "failed" };
+                warn!("API request {}, retrying in {:?} (attempt {}/{}): {}", reason, backoff, attempt, retry.max_retries, err);
+                sleep(backoff).await;
+                backoff *= 2;
+
+                if !may_retry() {
+                    return Err(err);
+                }
             },
-            _ => EventResult::Reject
+            Err(err) => return Err(err),
         }
-    });
+    }
 }
 
-fn register_focus_handler(ui: &AppWindow) {
-    let ui_handle = ui.as_weak();
-    ui.on_focus_change(move |has_focus| {
-        let ui_handle = ui_handle.clone();
-
-        tokio::spawn(async move {
-            // is_co2_focused is not yet updated at this point, but it is directly after.
-            // Hence, we wait just a tiny moment before checking it.
-            sleep(Duration::from_micros(5)).await;
+/// Turns a 401 response into an error, as its body is not an API response.
+fn check_unauthorized(resp: reqwest::Response) -> Result<reqwest::Response, reqwest::Error> {
+    if resp.status() == StatusCode::UNAUTHORIZED { resp.error_for_status() } else { Ok(resp) }
+}
 
-            let _ = ui_handle.upgrade_in_event_loop(move |ui| {
-                if has_focus || ui.get_is_co2_focused() {
-                    ui.set_window_opacity(WINDOW_OPACITY_FOCUSED);
-                } else {
-                    ui.set_window_opacity(WINDOW_OPACITY_UNFOCUSED);
-                }
-            });
-        });
-    });
+fn is_unauthorized(err: &reqwest::Error) -> bool {
+    err.status() == Some(StatusCode::UNAUTHORIZED)
 }
 
-fn start_ui_updater(ui: &AppWindow) {
-    // Periodically update the UI with the latest data from the API.
-    let ui_handle = ui.as_weak();
-    tokio::spawn(async move {
-        const UPDATE_INTERVAL: Duration = Duration::from_secs(15);
-        let mut interval = tokio::time::interval_at(Instant::now() + UPDATE_INTERVAL, UPDATE_INTERVAL);
+/// Whether the request did not get through, as opposed to the API answering with something we cannot use.
+/// Only these are worth retrying, the API would just give the same answer again.
+fn is_transport_error(err: &reqwest::Error) -> bool {
+    !is_unauthorized(err) && !err.is_decode()
+}
 
-        loop {
-            interval.tick().await; // Run every 15 seconds
+/// Logs a failed API request, calling out timeouts and rejected tokens separately.
+fn log_request_error(context: &str, err: &reqwest::Error) {
+    if err.is_timeout() {
+        error!("{}: request timed out: {}", context, err);
+    } else if is_unauthorized(err) {
+        error!("{}: the API rejected our credentials (401 Unauthorized)", context);
+    } else {
+        error!("{}: {:?}", context, err);
+    }
+}
 
-            match get_api_async(false).await {
-                Ok(resp) => try_apply_response(ui_handle.clone(), resp),
-                Err(err) => error!("Could not get metrics from API: {:?}", err),
-            }
-        }
-    });
+/// Why an API request failed, as told to the user.
+fn describe_request_error(err: &reqwest::Error) -> RequestFailure {
+    if err.is_timeout() {
+        RequestFailure::Timeout
+    } else if is_unauthorized(err) {
+        RequestFailure::Unauthorized
+    } else if err.is_connect() {
+        RequestFailure::Unreachable
+    } else if err.is_decode() {
+        RequestFailure::Unreadable
+    } else {
+        RequestFailure::Failed
+    }
 }
 
-/// Writes the options to disk in JSON format.
-fn save_options(options: &Options, path: &PathBuf) -> Result<()> {
-    let mut writer = BufWriter::new(fs::File::create(path)?);
-    serde_json::to_writer_pretty(&mut writer, options)?;
-    writer.flush()?;
-    Ok(())
+/// Connection settings for the thermostat API.
+/// Cloning is cheap, clones share the same connection pool.
+#[derive(Debug, Clone)]
+struct Api {
+    client: reqwest::Client,
+    endpoints: Arc<[ThermostatEndpoint]>, // Never empty.
+    auth: Option<HeaderValue>, // Marked sensitive, so it does not show up in debug output.
+    retry: RetryOptions,
+    timeout: Duration,
+    mock: Option<Arc<MockApi>>, // Answers all requests instead of the thermostats, see --mock.
 }
 
-/// Modify the thermostat config.
-fn modify_config(ui: &AppWindow, f: impl FnOnce(&mut ThermostatConfig)) {
-    let singletons = ui.global::<Singletons>(); // Get the Singletons module.
+impl Api {
+    fn new(options: &Options) -> Result<Self> {
+        let auth = options.api_token.as_ref()
+            .map(|token| {
+                let mut value = HeaderValue::from_str(&format!("Bearer {}", token))?;
+                value.set_sensitive(true);
+                Ok::<_, reqwest::header::InvalidHeaderValue>(value)
+            })
+            .transpose()?;
 
-    let mut cfg: ThermostatConfig = singletons.get_config().into(); // Get config.
-    f(&mut cfg); // Modify config.
-    singletons.set_config(cfg.into()); // Set config.
+        let mut headers = HeaderMap::new();
+        if let Some(auth) = &auth {
+            headers.insert(AUTHORIZATION, auth.clone());
+        }
 
-    update_config(ui, cfg);
-}
+        let timeout = Duration::from_millis(options.timeout_ms);
+        let proxy = load_proxy(options);
+        let builder = || {
+            let builder = reqwest::Client::builder()
+                .connect_timeout(timeout)
+                .timeout(timeout)
+                .default_headers(headers.clone());
+            match &proxy {
+                Some(proxy) => builder.proxy(proxy.clone()), // Replaces the proxy from the environment.
+                None => builder,
+            }
+        };
+        let client = match load_identity(options) {
+            Some(identity) => builder().identity(identity).build().or_else(|err| {
+                error!("Could not use the client certificate, connecting without one: {}", err);
+                builder().build()
+            })?,
+            None => builder().build()?,
+        };
 
-// Sends a PATCH request to the API to update the config.
-// This is done asynchronously.
-fn update_config(ui: &AppWindow, cfg: ThermostatConfig) {
-    let ui_handle = ui.as_weak();
-    tokio::spawn(async move {
-        // Send PATCH request to API
-        let res = patch_api_async(&reqwest::Client::new(), cfg).await;
+        let endpoints: Arc<[ThermostatEndpoint]> = options.endpoints().into();
+        Ok(Self {
+            client,
+            mock: options.mock.then(|| Arc::new(MockApi::new(endpoints.len()))),
+            endpoints,
+            auth,
+            retry: options.retry,
+            timeout,
+        })
+    }
 
-        if let Ok(resp) = res {
-            try_apply_response(ui_handle, resp);
-        } else {
-            error!("Error sending API request: {:?}", res.err());
+    fn url(&self, endpoint: usize) -> &str {
+        &self.endpoints[endpoint].api_url
+    }
+}
+
+/// Sets up the proxy from the options, if one is configured.
+/// Otherwise reqwest picks up `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY` from the environment by itself.
+fn load_proxy(options: &Options) -> Option<Proxy> {
+    let Some(proxy_url) = &options.proxy else {
+        let from_env = ["HTTPS_PROXY", "https_proxy", "HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"].into_iter()
+            .find_map(|var| std::env::var(var).ok().filter(|value| !value.is_empty()).map(|value| (var, value)));
+        match from_env {
+            Some((var, value)) => info!("Using proxy {} from {}", redact_url(&value), var),
+            None => info!("Not using a proxy"),
         }
-    });
+        return None;
+    };
+
+    match Proxy::all(proxy_url) {
+        Ok(proxy) => {
+            info!("Using proxy {}, except for {:?}", redact_url(proxy_url), options.no_proxy);
+            Some(proxy.no_proxy(NoProxy::from_string(&options.no_proxy.join(","))))
+        },
+        Err(err) => {
+            error!("Invalid proxy URL {}, connecting without a proxy: {}", redact_url(proxy_url), err);
+            None
+        },
+    }
 }
 
-fn try_apply_response(ui_handle: Weak<AppWindow>, resp: APIResponse) {
-    if resp.success {
-        // Ignore result, we don't care if it actually updated.
-        // If it didn't, the UI is probably gone anyway.
-        let _ = ui_handle.upgrade_in_event_loop(move |ui| {
-            ui.global::<Singletons>().set_state(resp.data.unwrap().state.into());
-        });
-    } else {
-        error!("API returned an error: {}", resp.error.unwrap());
+/// `url` without the password, if it has one, so it can be logged.
+fn redact_url(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(mut url) if url.password().is_some() => {
+            let _ = url.set_password(Some("***"));
+            url.to_string()
+        },
+        Ok(url) => url.to_string(),
+        Err(_) => "(unparsable)".to_owned(), // Could hold a password anywhere.
     }
 }
 
-/// Send a PATCH request to the API.
-async fn patch_api_async(client: &reqwest::Client, new_config: ThermostatConfig) -> Result<APIResponse, reqwest::Error> {
-    info!("Updating config to {:?}", new_config);
+/// Loads the client certificate for mutual TLS, if one is configured.
+/// A missing or invalid one is logged and skipped, the API may well work without.
+fn load_identity(options: &Options) -> Option<Identity> {
+    let (cert_path, key_path) = match (&options.client_cert, &options.client_key) {
+        (Some(cert_path), Some(key_path)) => (cert_path, key_path),
+        (None, None) => return None,
+        _ => {
+            error!("Both client_cert and client_key are needed for a client certificate, connecting without one");
+            return None;
+        },
+    };
 
-    client.patch(API_URL)
-        .json(&new_config)
-        .send()
-        .await?
-        .json::<APIResponse>()
-        .await
-}
+    let read = |path: &Path| fs::read(path)
+        .map_err(|err| error!("Could not read {:?}, connecting without a client certificate: {}", path, err))
+        .ok();
+    let (cert, key) = (read(cert_path)?, read(key_path)?);
 
-/// Get the current thermostat config and states from the API.
-async fn get_api_async(include_config: bool) -> Result<APIResponse, reqwest::Error> {
-    reqwest::get(API_URL.to_owned() + "?include_config=" + &include_config.to_string())
-        .await?
-        .json()
-        .await
+    match Identity::from_pkcs8_pem(&cert, &key) {
+        Ok(identity) => {
+            info!("Using client certificate {:?}", cert_path);
+            Some(identity)
+        },
+        Err(err) => {
+            error!("Invalid client certificate {:?} or key {:?}, connecting without one: {}", cert_path, key_path, err);
+            None
+        },
+    }
 }
 
 // Thermostat config
 #[derive(serde::Deserialize, serde::Serialize)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 struct ThermostatConfig {
     master_switch: bool,
     force: bool,
@@ -310,6 +1933,89 @@ struct ThermostatConfig {
     co2_target: Option<i32>,
 }
 
+/// Body of a config update, leaving out what did not change so updates from other clients to those fields are not overwritten.
+#[derive(serde::Serialize, Debug)]
+struct ConfigPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    master_switch: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    force: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target_temp: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    co2_target: Option<Option<i32>>, // Null to stop requiring CO₂.
+}
+
+impl ConfigPatch {
+    /// The fields of `cfg` that differ from `base`, or all of them if the config of the server is not known yet
+    /// or nothing differs, e.g. when sending the same config again to be sure.
+    fn new(cfg: ThermostatConfig, base: Option<ThermostatConfig>) -> Self {
+        let full = Self { master_switch: Some(cfg.master_switch), force: Some(cfg.force), target_temp: Some(cfg.target_temp), co2_target: Some(cfg.co2_target) };
+        match base {
+            Some(base) if base != cfg => Self {
+                master_switch: (cfg.master_switch != base.master_switch).then_some(cfg.master_switch),
+                force: (cfg.force != base.force).then_some(cfg.force),
+                target_temp: (cfg.target_temp != base.target_temp).then_some(cfg.target_temp),
+                co2_target: (cfg.co2_target != base.co2_target).then_some(cfg.co2_target),
+            },
+            _ => full,
+        }
+    }
+}
+
+#[cfg(test)]
+mod config_patch_tests {
+    use super::*;
+    use serde_json::json;
+
+    const CFG: ThermostatConfig = ThermostatConfig { master_switch: true, force: false, target_temp: 20.0, co2_target: Some(800) };
+
+    fn body(cfg: ThermostatConfig, base: Option<ThermostatConfig>) -> serde_json::Value {
+        serde_json::to_value(ConfigPatch::new(cfg, base)).unwrap()
+    }
+
+    #[test]
+    fn sends_only_what_differs() {
+        let cfg = ThermostatConfig { target_temp: 21.5, force: true, ..CFG };
+        assert_eq!(body(cfg, Some(CFG)), json!({ "target_temp": 21.5, "force": true }));
+    }
+
+    #[test]
+    fn sends_null_to_stop_requiring_co2() {
+        let cfg = ThermostatConfig { co2_target: None, ..CFG };
+        assert_eq!(body(cfg, Some(CFG)), json!({ "co2_target": null }));
+    }
+
+    #[test]
+    fn sends_a_changed_co2_target() {
+        let cfg = ThermostatConfig { co2_target: Some(1000), ..CFG };
+        assert_eq!(body(cfg, Some(CFG)), json!({ "co2_target": 1000 }));
+    }
+
+    #[test]
+    fn sends_everything_without_a_base_or_a_difference() {
+        let full = json!({ "master_switch": true, "force": false, "target_temp": 20.0, "co2_target": 800 });
+        assert_eq!(body(CFG, None), full);
+        assert_eq!(body(CFG, Some(CFG)), full);
+    }
+}
+
+impl ThermostatConfig {
+    /// Checks that the config is within what the app allows, so nothing nonsensical is ever sent to the API.
+    fn validate(&self, options: &AppOptions) -> Result<()> {
+        if !self.target_temp.is_finite() {
+            bail!("target temperature {} is not a number", self.target_temp);
+        }
+        if !(options.min_target_temp..=options.max_target_temp).contains(&self.target_temp) {
+            bail!("target temperature {} is outside of {} to {}", self.target_temp, options.min_target_temp, options.max_target_temp);
+        }
+        if let Some(co2_target) = self.co2_target.filter(|co2_target| !(MIN_CO2_TARGET..=MAX_CO2_TARGET).contains(co2_target)) {
+            bail!("CO₂ target {} is outside of {} to {} ppm", co2_target, MIN_CO2_TARGET, MAX_CO2_TARGET);
+        }
+        Ok(())
+    }
+}
+
 // Allow for conversion between the slint-generated Config struct and the ThermostatConfig struct.
 impl From<Config> for ThermostatConfig {
     fn from(cfg: Config) -> Self {
@@ -317,7 +2023,7 @@ impl From<Config> for ThermostatConfig {
             master_switch: cfg.master_switch,
             force: cfg.force,
             target_temp: cfg.target_temp,
-            co2_target: if cfg.require_co2 {Some(cfg.co2_target)} else {None},
+            co2_target: cfg.require_co2.then(|| cfg.co2_target.clamp(MIN_CO2_TARGET, MAX_CO2_TARGET)),
         }
     }
 }
@@ -329,7 +2035,7 @@ impl From<ThermostatConfig> for Config {
             force: cfg.force,
             target_temp: cfg.target_temp,
             require_co2: cfg.co2_target.is_some(),
-            co2_target: cfg.co2_target.unwrap_or(500),
+            co2_target: cfg.co2_target.unwrap_or_default(), // Unused while CO₂ is not required, the UI shows the last target then.
         }
     }
 }
@@ -339,20 +2045,24 @@ struct APIResponse {
     success: bool,
     data: Option<APIResponseData>,
     error: Option<String>,
+    time: Option<i64>, // Unix time in seconds the server answered at, if it says.
 }
 
 #[derive(serde::Deserialize, Debug)]
 struct APIResponseData {
+    #[serde(default, deserialize_with = "schema::lenient_config")]
     config: Option<ThermostatConfig>,
     state: APIResponseStateData,
 }
 
-#[derive(serde::Deserialize, Debug)]
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+#[serde(from = "schema::RawState")] // Keeps showing what it can of a state that changed shape.
 struct APIResponseStateData {
     available: bool,
     temperature: f32,
     co2: i32,
     is_heating: bool,
+    humidity: Option<f32>, // Not reported by older servers.
 }
 
 impl From<APIResponseStateData> for State {
@@ -362,23 +2072,337 @@ impl From<APIResponseStateData> for State {
             current_temp: state.temperature,
             co2: state.co2,
             is_heating: state.is_heating,
+            has_humidity: state.humidity.is_some(),
+            humidity: state.humidity.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<State> for APIResponseStateData {
+    fn from(state: State) -> Self {
+        Self {
+            available: state.available,
+            temperature: state.current_temp,
+            co2: state.co2,
+            is_heating: state.is_heating,
+            humidity: state.has_humidity.then_some(state.humidity),
         }
     }
 }
 
+/// Last known config and state, shown on startup until the API responds.
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+struct CachedState {
+    config: ThermostatConfig,
+    state: APIResponseStateData,
+}
+
 #[derive(serde::Deserialize, serde::Serialize, Debug)]
+#[serde(default)] // Fill in missing fields from older options files with their defaults.
 struct Options {
+    version: u32,
     #[serde(with = "PhysicalPositionRemote")]
     window_pos: PhysicalPosition,
+    #[serde(with = "PhysicalSizeRemote")]
+    window_size: PhysicalSize,
+    monitor: Option<MonitorSelector>, // Monitor the window is always placed on, regardless of window_pos.
     #[serde(with = "AppOptionsRemote")]
     app_options: AppOptions,
+    api_url: String, // Only used if no thermostats are listed.
+    thermostats: Vec<ThermostatEndpoint>,
+    selected_thermostat: usize,
+    retry: RetryOptions,
+    timeout_ms: u64,
+    schedule: Vec<ScheduleEntry>,
+    weekday_targets: HashMap<Weekday, f32>, // Default target temperature set when each of these days starts.
+    co2_presets: Vec<i32>, // CO₂ targets in ppm to cycle through with a key.
+    keymap: HashMap<Shortcut, String>, // Keys replacing the default ones of these shortcuts, e.g. "Ctrl+F".
+    api_token: Option<String>, // Sent as bearer token, never logged.
+    client_cert: Option<PathBuf>, // PEM certificate for mutual TLS, only used together with client_key.
+    client_key: Option<PathBuf>, // PEM PKCS #8 private key of client_cert.
+    last_co2_targets: HashMap<usize, i32>, // CO₂ target in ppm restored when requiring CO₂ again, by thermostat.
+    proxy: Option<String>, // URL of the proxy to send all requests through, instead of the one from the environment.
+    no_proxy: Vec<String>, // Hosts, domains and IP ranges to reach without the proxy, e.g. the thermostat's local address.
+    metrics_file: Option<PathBuf>, // Where to write the latest states for dashboards, as Prometheus text if it ends in .prom.
+    #[serde(skip)]
+    api_url_override: Option<String>, // Set with --api-url, never saved.
+    #[serde(skip)]
+    mock: bool, // Set with --mock, never saved.
+    #[serde(skip)]
+    first_run: bool, // Whether there was no options file yet, so window_pos is no position the user chose.
 }
 
 impl Default for Options {
     fn default() -> Self {
         Self {
-            window_pos: PhysicalPosition { x: 190, y: 190 },
-            app_options: AppOptions::default(),
+            version: migrate::OPTIONS_VERSION,
+            window_pos: DEFAULT_WINDOW_POS,
+            window_size: PhysicalSize { width: 300, height: 400 },
+            monitor: None,
+            app_options: default_app_options(),
+            api_url: DEFAULT_API_URL.to_owned(),
+            thermostats: Vec::new(),
+            selected_thermostat: 0,
+            retry: RetryOptions::default(),
+            timeout_ms: 5000,
+            schedule: Vec::new(),
+            weekday_targets: HashMap::new(),
+            co2_presets: DEFAULT_CO2_PRESETS.to_vec(),
+            keymap: HashMap::new(),
+            api_token: None,
+            client_cert: None,
+            client_key: None,
+            last_co2_targets: HashMap::new(),
+            proxy: None,
+            no_proxy: Vec::new(),
+            metrics_file: None,
+            api_url_override: None,
+            mock: false,
+            first_run: false,
+        }
+    }
+}
+
+impl Options {
+    /// The thermostats to control, falling back to a single one at `api_url` if none are listed.
+    /// A URL from the command line takes precedence over both.
+    fn endpoints(&self) -> Vec<ThermostatEndpoint> {
+        if let Some(api_url) = &self.api_url_override {
+            return vec![ThermostatEndpoint { name: "Thermostat".to_owned(), api_url: api_url.clone() }];
+        }
+
+        if !self.thermostats.is_empty() {
+            return self.thermostats.clone();
+        }
+
+        vec![ThermostatEndpoint { name: "Thermostat".to_owned(), api_url: self.api_url.clone() }]
+    }
+
+    /// Index of the thermostat to select on startup.
+    fn selected_endpoint(&self) -> usize {
+        if self.api_url_override.is_some() { 0 } else { self.selected_thermostat }
+    }
+
+    /// Replaces invalid values read from disk with their defaults.
+    fn validate(&mut self) {
+        if !is_valid_api_url(&self.api_url) {
+            warn!("Invalid API URL {:?}, falling back to {}", self.api_url, DEFAULT_API_URL);
+            self.api_url = DEFAULT_API_URL.to_owned();
+        }
+
+        self.thermostats.retain(|endpoint| {
+            let valid = is_valid_api_url(&endpoint.api_url);
+            if !valid {
+                warn!("Invalid API URL {:?} of thermostat {}, ignoring it", endpoint.api_url, endpoint.name);
+            }
+            valid
+        });
+
+        let count = self.endpoints().len();
+        if self.selected_thermostat >= count {
+            warn!("Selected thermostat {} does not exist, falling back to the first one", self.selected_thermostat);
+            self.selected_thermostat = 0;
+        }
+
+        if self.timeout_ms == 0 {
+            warn!("Request timeout must be greater than zero, falling back to the default");
+            self.timeout_ms = Self::default().timeout_ms;
+        }
+
+        let token_valid = self.api_token.as_ref()
+            .map(|token| !token.is_empty() && HeaderValue::from_str(&format!("Bearer {}", token)).is_ok());
+        if token_valid == Some(false) {
+            warn!("API token is empty or contains invalid characters, not sending one");
+            self.api_token = None;
+        }
+
+        let step = self.app_options.temperature_step;
+        if !(step.is_finite() && step > 0.0) {
+            warn!("Temperature step must be greater than zero, falling back to {}", DEFAULT_TEMPERATURE_STEP);
+            self.app_options.temperature_step = DEFAULT_TEMPERATURE_STEP;
+        }
+
+        let (min, max) = (self.app_options.min_target_temp, self.app_options.max_target_temp);
+        if !(min.is_finite() && max.is_finite() && min < max) {
+            warn!("Invalid target temperature range {}..{}, falling back to {}..{}", min, max, DEFAULT_MIN_TARGET_TEMP, DEFAULT_MAX_TARGET_TEMP);
+            self.app_options.min_target_temp = DEFAULT_MIN_TARGET_TEMP;
+            self.app_options.max_target_temp = DEFAULT_MAX_TARGET_TEMP;
+        }
+
+        // Checked after the range, so it can be held against a valid one.
+        let default_target = self.app_options.default_target_temp;
+        if !(self.app_options.min_target_temp..=self.app_options.max_target_temp).contains(&default_target) {
+            let fallback = DEFAULT_TARGET_TEMP.clamp(self.app_options.min_target_temp, self.app_options.max_target_temp);
+            warn!("Default target temperature {} is outside of the target range, falling back to {}", default_target, fallback);
+            self.app_options.default_target_temp = fallback;
+        }
+
+        let (min_target_temp, max_target_temp) = (self.app_options.min_target_temp, self.app_options.max_target_temp);
+        self.weekday_targets.retain(|weekday, target_temp| {
+            let valid = (min_target_temp..=max_target_temp).contains(target_temp);
+            if !valid {
+                warn!("Default target temperature {} of {} is outside of the target range, ignoring it", target_temp, weekday);
+            }
+            valid
+        });
+
+        self.keymap.retain(|shortcut, key| {
+            let valid = shortcuts::Key::parse(key).is_some();
+            if !valid {
+                warn!("Key {:?} for shortcut {:?} is not a key, keeping the default", key, shortcut);
+            }
+            valid
+        });
+
+        self.co2_presets.retain(|co2_target| {
+            let valid = (MIN_CO2_TARGET..=MAX_CO2_TARGET).contains(co2_target);
+            if !valid {
+                warn!("CO₂ preset {} ppm is outside of {} to {} ppm, ignoring it", co2_target, MIN_CO2_TARGET, MAX_CO2_TARGET);
+            }
+            valid
+        });
+
+        if self.app_options.co2_alert_margin < 0 {
+            warn!("CO₂ alert margin cannot be negative, falling back to {}", DEFAULT_CO2_ALERT_MARGIN);
+            self.app_options.co2_alert_margin = DEFAULT_CO2_ALERT_MARGIN;
+        }
+
+        if self.app_options.co2_good_below <= 0 || self.app_options.co2_poor_above < self.app_options.co2_good_below {
+            warn!("CO₂ color thresholds must be positive and in order, falling back to {} and {} ppm", DEFAULT_CO2_GOOD_BELOW, DEFAULT_CO2_POOR_ABOVE);
+            self.app_options.co2_good_below = DEFAULT_CO2_GOOD_BELOW;
+            self.app_options.co2_poor_above = DEFAULT_CO2_POOR_ABOVE;
+        }
+
+        if !(MIN_CO2_TARGET..=MAX_CO2_TARGET).contains(&self.app_options.default_co2_target) {
+            warn!("Default CO₂ target must be between {} and {} ppm, falling back to {} ppm", MIN_CO2_TARGET, MAX_CO2_TARGET, DEFAULT_CO2_TARGET);
+            self.app_options.default_co2_target = DEFAULT_CO2_TARGET;
+        }
+
+        if self.app_options.max_updates_per_minute < 0 {
+            warn!("Update limit cannot be negative, falling back to {} per minute", DEFAULT_MAX_UPDATES_PER_MINUTE);
+            self.app_options.max_updates_per_minute = DEFAULT_MAX_UPDATES_PER_MINUTE;
+        }
+
+        if !self.app_options.frost_protection_temp.is_finite() {
+            warn!("Invalid frost protection temperature, falling back to {}", DEFAULT_FROST_PROTECTION_TEMP);
+            self.app_options.frost_protection_temp = DEFAULT_FROST_PROTECTION_TEMP;
+        }
+
+        if !self.app_options.boost_delta.is_finite() || self.app_options.boost_delta <= 0.0 {
+            warn!("Boost must raise the target by a positive amount, falling back to {}", DEFAULT_BOOST_DELTA);
+            self.app_options.boost_delta = DEFAULT_BOOST_DELTA;
+        }
+
+        if self.app_options.boost_minutes <= 0 {
+            warn!("Boost duration must be positive, falling back to {} minutes", DEFAULT_BOOST_MINUTES);
+            self.app_options.boost_minutes = DEFAULT_BOOST_MINUTES;
+        }
+
+        if !(0.0..=MAX_CO2_SMOOTHING).contains(&self.app_options.co2_smoothing) {
+            warn!("CO₂ smoothing must be between 0 and {}, falling back to no smoothing", MAX_CO2_SMOOTHING);
+            self.app_options.co2_smoothing = 0.0;
+        }
+
+        if self.app_options.max_clock_skew_secs < 0 {
+            warn!("The allowed clock skew cannot be negative, falling back to {} seconds", DEFAULT_MAX_CLOCK_SKEW_SECS);
+            self.app_options.max_clock_skew_secs = DEFAULT_MAX_CLOCK_SKEW_SECS;
+        }
+
+        if self.app_options.hold_minutes <= 0 {
+            warn!("Hold duration must be positive, falling back to {} minutes", DEFAULT_HOLD_MINUTES);
+            self.app_options.hold_minutes = DEFAULT_HOLD_MINUTES;
+        }
+
+        self.last_co2_targets.retain(|endpoint, co2_target| {
+            let valid = (MIN_CO2_TARGET..=MAX_CO2_TARGET).contains(co2_target);
+            if !valid {
+                warn!("Last CO₂ target {} ppm of thermostat {} is out of range, falling back to the default CO₂ target", co2_target, endpoint);
+            }
+            valid
+        });
+
+        if self.app_options.force_timeout_minutes < 0 {
+            warn!("Force on timeout cannot be negative, falling back to never turning force on off");
+            self.app_options.force_timeout_minutes = 0;
+        }
+
+        if self.app_options.toggle_cooldown_ms < 0 {
+            warn!("Toggle cooldown cannot be negative, falling back to {}ms", DEFAULT_TOGGLE_COOLDOWN_MS);
+            self.app_options.toggle_cooldown_ms = DEFAULT_TOGGLE_COOLDOWN_MS;
+        }
+
+        if self.app_options.debounce_ms < 0 {
+            warn!("Debounce duration cannot be negative, falling back to {}ms", DEFAULT_DEBOUNCE_MS);
+            self.app_options.debounce_ms = DEFAULT_DEBOUNCE_MS;
+        }
+
+        self.app_options.opacity_focused = valid_opacity(self.app_options.opacity_focused, DEFAULT_OPACITY_FOCUSED);
+        self.app_options.opacity_unfocused = valid_opacity(self.app_options.opacity_unfocused, DEFAULT_OPACITY_UNFOCUSED);
+
+        if self.app_options.poll_interval_secs < MIN_POLL_INTERVAL_SECS {
+            warn!("Poll interval must be at least {}s, falling back to {}s", MIN_POLL_INTERVAL_SECS, DEFAULT_POLL_INTERVAL_SECS);
+            self.app_options.poll_interval_secs = DEFAULT_POLL_INTERVAL_SECS;
+        }
+
+        if self.app_options.opacity_fade_ms < 0 {
+            warn!("Opacity fade duration cannot be negative, falling back to {}ms", DEFAULT_OPACITY_FADE_MS);
+            self.app_options.opacity_fade_ms = DEFAULT_OPACITY_FADE_MS;
+        }
+
+        let ui_scale = self.app_options.ui_scale;
+        if !(MIN_UI_SCALE..=MAX_UI_SCALE).contains(&ui_scale) {
+            let clamped = if ui_scale.is_nan() { 1.0 } else { ui_scale.clamp(MIN_UI_SCALE, MAX_UI_SCALE) };
+            warn!("UI scale {} is outside of {} to {}, using {}", ui_scale, MIN_UI_SCALE, MAX_UI_SCALE, clamped);
+            self.app_options.ui_scale = clamped;
+        }
+    }
+}
+
+/// Clamps an opacity to 0.0 to 1.0, falling back to `default` if it is not a number at all.
+fn valid_opacity(opacity: f32, default: f32) -> f32 {
+    if opacity.is_nan() {
+        warn!("Window opacity is not a number, falling back to {}", default);
+        return default;
+    }
+    opacity.clamp(0.0, 1.0)
+}
+
+/// Any http or https URL with a host, which can be a name, e.g. `thermostat.local`, an IPv4 address or an IPv6 address in brackets.
+fn is_valid_api_url(url: &str) -> bool {
+    reqwest::Url::parse(url).is_ok_and(|url| (url.scheme() == "http" || url.scheme() == "https") && url.host().is_some())
+}
+
+/// Logs what the host of an API URL resolves to, or why it does not.
+/// Names are resolved by the OS, which also resolves mDNS names like `thermostat.local` where it supports mDNS.
+async fn log_resolved_host(api_url: &str) {
+    let Some((host, port)) = reqwest::Url::parse(api_url).ok()
+        .and_then(|url| Some((url.host_str()?.to_owned(), url.port_or_known_default()?))) else { return };
+    if host.starts_with('[') || host.parse::<std::net::Ipv4Addr>().is_ok() {
+        return; // An address already.
+    }
+
+    match tokio::net::lookup_host((host.as_str(), port)).await {
+        Ok(addrs) => info!("{} resolves to {:?}", host, addrs.map(|addr| addr.ip()).collect::<Vec<_>>()),
+        Err(err) if host.ends_with(".local") => {
+            warn!("Could not resolve {}, the OS may not support mDNS (on Linux, install nss-mdns): {}", host, err);
+        },
+        Err(err) => warn!("Could not resolve {}: {}", host, err),
+    };
+}
+
+/// How often and how quickly failed API requests are retried.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy)]
+#[serde(default)]
+struct RetryOptions {
+    max_retries: u32,
+    initial_backoff_ms: u64,
+}
+
+impl Default for RetryOptions {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff_ms: 250,
         }
     }
 }
@@ -391,7 +2415,111 @@ struct PhysicalPositionRemote {
 }
 
 #[derive(serde::Deserialize, serde::Serialize)]
-#[serde(remote = "AppOptions")]
+#[serde(remote = "PhysicalSize")]
+struct PhysicalSizeRemote {
+    width: u32,
+    height: u32,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(remote = "AppOptions", default = "default_app_options")]
 struct AppOptionsRemote {
     on_top: bool,
+    #[serde(with = "TemperatureUnitRemote")]
+    temperature_unit: TemperatureUnit,
+    temperature_step: f32,
+    min_target_temp: f32,
+    max_target_temp: f32,
+    hotkey: SharedString,
+    notify_on_target: bool,
+    co2_alert_threshold: i32,
+    co2_alert_margin: i32,
+    debounce_ms: i32,
+    #[serde(with = "ThemeRemote")]
+    theme: Theme,
+    confirm_master_off: bool,
+    opacity_focused: f32,
+    opacity_unfocused: f32,
+    opacity_fade_ms: i32,
+    auto_start: bool,
+    edge_snapping: bool,
+    poll_interval_secs: i32,
+    read_only: bool,
+    default_target_temp: f32,
+    compact: bool,
+    default_co2_target: i32,
+    heating_accent: bool,
+    force_timeout_minutes: i32,
+    ui_scale: f32,
+    toggle_cooldown_ms: i32,
+    co2_good_below: i32,
+    co2_poor_above: i32,
+    hold_minutes: i32,
+    max_updates_per_minute: i32,
+    frost_protection: bool,
+    frost_protection_temp: f32,
+    boost_delta: f32,
+    boost_minutes: i32,
+    co2_smoothing: f32,
+    click_through: bool,
+    optimistic_updates: bool,
+    language: SharedString,
+    max_clock_skew_secs: i32,
+}
+
+/// Slint structs cannot have default values, so the defaults for the app options live here.
+fn default_app_options() -> AppOptions {
+    AppOptions {
+        temperature_step: DEFAULT_TEMPERATURE_STEP,
+        min_target_temp: DEFAULT_MIN_TARGET_TEMP,
+        max_target_temp: DEFAULT_MAX_TARGET_TEMP,
+        hotkey: DEFAULT_HOTKEY.into(),
+        co2_alert_margin: DEFAULT_CO2_ALERT_MARGIN,
+        debounce_ms: DEFAULT_DEBOUNCE_MS,
+        opacity_focused: DEFAULT_OPACITY_FOCUSED,
+        opacity_unfocused: DEFAULT_OPACITY_UNFOCUSED,
+        opacity_fade_ms: DEFAULT_OPACITY_FADE_MS,
+        edge_snapping: true,
+        poll_interval_secs: DEFAULT_POLL_INTERVAL_SECS,
+        default_target_temp: DEFAULT_TARGET_TEMP,
+        default_co2_target: DEFAULT_CO2_TARGET,
+        heating_accent: true,
+        ui_scale: 1.0,
+        toggle_cooldown_ms: DEFAULT_TOGGLE_COOLDOWN_MS,
+        co2_good_below: DEFAULT_CO2_GOOD_BELOW,
+        co2_poor_above: DEFAULT_CO2_POOR_ABOVE,
+        hold_minutes: DEFAULT_HOLD_MINUTES,
+        max_updates_per_minute: DEFAULT_MAX_UPDATES_PER_MINUTE,
+        frost_protection: true,
+        frost_protection_temp: DEFAULT_FROST_PROTECTION_TEMP,
+        boost_delta: DEFAULT_BOOST_DELTA,
+        boost_minutes: DEFAULT_BOOST_MINUTES,
+        optimistic_updates: true,
+        max_clock_skew_secs: DEFAULT_MAX_CLOCK_SKEW_SECS,
+        ..Default::default()
+    }
+}
+
+/// A monitor by its index among the connected ones or by its name, as logged on startup.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+#[serde(untagged)]
+enum MonitorSelector {
+    Index(usize),
+    Name(String),
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(remote = "Theme")]
+enum ThemeRemote {
+    System,
+    Dark,
+    Light,
+    HighContrast,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(remote = "TemperatureUnit")]
+enum TemperatureUnitRemote {
+    Celsius,
+    Fahrenheit,
 }
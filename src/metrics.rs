@@ -0,0 +1,75 @@
+use std::{cell::{Cell, RefCell}, collections::HashMap, io::Write, path::PathBuf};
+use log::{error, info};
+use slint::{ComponentHandle, Model};
+
+use crate::{write_atomically, write_json, APIResponseStateData, AppWindow, Singletons};
+
+thread_local! {
+    // Only ever touched from the UI thread.
+    static PATH: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+    static FAILING: Cell<bool> = const { Cell::new(false) }; // So a file that cannot be written is not logged on every poll.
+}
+
+/// A thermostat as written to the JSON metrics file.
+#[derive(serde::Serialize)]
+struct Metrics<'a> {
+    name: String,
+    #[serde(flatten)]
+    state: &'a APIResponseStateData,
+}
+
+/// Has the states of all thermostats written to `path` whenever one is received, for dashboards to scrape.
+/// Written as Prometheus text if the file name ends in `.prom`, as JSON otherwise.
+pub fn init(path: Option<PathBuf>) {
+    if let Some(path) = &path {
+        info!("Writing metrics to {:?}", path);
+    }
+    PATH.set(path);
+}
+
+/// Writes the latest states, by the index of the thermostat in the options.
+pub fn write(ui: &AppWindow, states: &HashMap<usize, APIResponseStateData>) {
+    let Some(path) = PATH.with_borrow(Clone::clone) else { return };
+
+    let names = ui.global::<Singletons>().get_thermostat_names();
+    let name = |endpoint: usize| names.row_data(endpoint).map_or_else(|| endpoint.to_string(), String::from);
+    let mut states: Vec<_> = states.iter().map(|(&endpoint, state)| Metrics { name: name(endpoint), state }).collect();
+    states.sort_by(|a, b| a.name.cmp(&b.name)); // Stable output, so diffs of the file only show what changed.
+
+    let result = if path.extension().is_some_and(|extension| extension == "prom") {
+        write_atomically(&path, |writer| Ok(writer.write_all(to_prometheus(&states).as_bytes())?))
+    } else {
+        write_json(&states, &path)
+    };
+
+    match result {
+        Ok(()) => FAILING.set(false),
+        Err(err) if !FAILING.replace(true) => error!("Could not write metrics to {:?}: {:?}", path, err),
+        Err(_) => {},
+    }
+}
+
+/// The states in the Prometheus text exposition format, leaving out the readings of unavailable sensors.
+fn to_prometheus(states: &[Metrics]) -> String {
+    let mut text = String::new();
+    let mut gauge = |metric: &str, help: &str, value: &dyn Fn(&APIResponseStateData) -> Option<f32>| {
+        text += &format!("# HELP {} {}\n# TYPE {} gauge\n", metric, help, metric);
+        for metrics in states {
+            if let Some(value) = value(metrics.state) {
+                text += &format!("{}{{thermostat=\"{}\"}} {}\n", metric, escape_label(&metrics.name), value);
+            }
+        }
+    };
+
+    let available = |state: &APIResponseStateData| state.available;
+    gauge("thermostat_available", "Whether the sensor of the thermostat is available.", &|state| Some(available(state) as u8 as f32));
+    gauge("thermostat_temperature_celsius", "Current temperature.", &|state| available(state).then_some(state.temperature));
+    gauge("thermostat_co2_ppm", "Current CO₂ concentration.", &|state| available(state).then_some(state.co2 as f32));
+    gauge("thermostat_humidity_percent", "Current relative humidity.", &|state| state.humidity.filter(|_| available(state)));
+    gauge("thermostat_heating", "Whether the thermostat is heating.", &|state| Some(state.is_heating as u8 as f32));
+    text
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
@@ -0,0 +1,135 @@
+use std::{fs, path::Path};
+use anyhow::{bail, Context, Result};
+use log::{error, info, warn};
+use serde_json::{json, Map, Value};
+
+use crate::Options;
+
+/// Version of the options file written by this build, bumped whenever its structure changes.
+pub const OPTIONS_VERSION: u32 = 2;
+
+/// Reads the options from disk, upgrading files written by older versions.
+/// Fields that cannot be read fall back to their defaults instead of discarding the whole file.
+/// Whenever the file is not read as-is, a backup of it is kept next to it.
+pub fn load_options(path: &Path) -> Options {
+    let Ok(contents) = fs::read_to_string(path) else { return Options::default() };
+
+    let mut value = match serde_json::from_str::<Value>(&contents) {
+        Ok(Value::Object(value)) => value,
+        Ok(_) | Err(_) => {
+            error!("Options file is not a JSON object, falling back to the defaults");
+            back_up(path);
+            return Options::default();
+        },
+    };
+
+    let version = value.get("version").and_then(Value::as_u64).unwrap_or(0) as u32;
+    if version > OPTIONS_VERSION {
+        warn!("Options file is of a newer version ({}) than this build supports ({}), unknown fields are dropped", version, OPTIONS_VERSION);
+    }
+    if version != OPTIONS_VERSION {
+        back_up(path);
+    }
+
+    for from in version..OPTIONS_VERSION {
+        info!("Migrating options from version {} to {}", from, from + 1);
+        migrate(&mut value, from);
+    }
+    value.insert("version".to_owned(), OPTIONS_VERSION.into());
+
+    let value = Value::Object(value);
+    if let Ok(options) = serde_json::from_value(value.clone()) {
+        return options;
+    }
+
+    // Read what we can, field by field.
+    let mut merged = serde_json::to_value(Options::default()).expect("default options are serializable");
+    let mut dropped = false;
+    merge(&mut merged, &mut Vec::new(), &value, &mut dropped);
+    if dropped && version == OPTIONS_VERSION {
+        back_up(path);
+    }
+
+    serde_json::from_value(merged).unwrap_or_else(|err| {
+        error!("Could not read options from disk: {:?}", err);
+        Options::default()
+    })
+}
+
+/// Reads options exported from another installation, upgrading them if they are of an older version.
+/// Unlike `load_options`, anything that cannot be read is an error, so a broken file never replaces working options.
+pub fn import_options(path: &Path) -> Result<Options> {
+    let contents = fs::read_to_string(path).with_context(|| format!("could not read {:?}", path))?;
+    let Value::Object(mut value) = serde_json::from_str::<Value>(&contents)? else { bail!("{:?} is not a JSON object", path) };
+
+    let version = value.get("version").and_then(Value::as_u64).unwrap_or(0) as u32;
+    if version > OPTIONS_VERSION {
+        bail!("{:?} is of a newer version ({}) than this build supports ({})", path, version, OPTIONS_VERSION);
+    }
+    for from in version..OPTIONS_VERSION {
+        info!("Migrating imported options from version {} to {}", from, from + 1);
+        migrate(&mut value, from);
+    }
+    value.insert("version".to_owned(), OPTIONS_VERSION.into());
+
+    Ok(serde_json::from_value(Value::Object(value))?)
+}
+
+/// Upgrades options of `version` to the next version.
+fn migrate(options: &mut Map<String, Value>, version: u32) {
+    match version {
+        // Version 0 predates multiple thermostats, its API URL becomes the only thermostat.
+        0 => {
+            if let Some(api_url) = options.get("api_url").filter(|_| !options.contains_key("thermostats")).cloned() {
+                options.insert("thermostats".to_owned(), json!([{ "name": "Thermostat", "api_url": api_url }]));
+            }
+        },
+        // Version 1 remembered a single last CO₂ target, it becomes that of every thermostat.
+        1 => {
+            if let Some(last_co2_target) = options.remove("last_co2_target").filter(|target| !target.is_null() && !options.contains_key("last_co2_targets")) {
+                let count = options.get("thermostats").and_then(Value::as_array).map_or(1, Vec::len);
+                let targets: Map<String, Value> = (0..count).map(|endpoint| (endpoint.to_string(), last_co2_target.clone())).collect();
+                options.insert("last_co2_targets".to_owned(), Value::Object(targets));
+            }
+        },
+        _ => unreachable!("no migration from options version {}", version),
+    }
+}
+
+/// Copies `value` into `merged` at `path`, keeping only the fields that still deserialize.
+/// Objects that do not deserialize as a whole are merged field by field.
+fn merge(merged: &mut Value, path: &mut Vec<String>, value: &Value, dropped: &mut bool) {
+    let mut candidate = merged.clone();
+    *pointer(&mut candidate, path) = value.clone();
+    if serde_json::from_value::<Options>(candidate.clone()).is_ok() {
+        *merged = candidate;
+        return;
+    }
+
+    match (value, pointer(merged, path)) {
+        (Value::Object(fields), Value::Object(_)) => {
+            for (key, field) in fields {
+                path.push(key.clone());
+                merge(merged, path, field, dropped);
+                path.pop();
+            }
+        },
+        _ => {
+            warn!("Could not read option {}, using its default", path.join("."));
+            *dropped = true;
+        },
+    }
+}
+
+/// The value at `path`, inserted as null if it does not exist yet.
+fn pointer<'a>(value: &'a mut Value, path: &[String]) -> &'a mut Value {
+    path.iter().fold(value, |value, key| &mut value[key.as_str()])
+}
+
+pub fn back_up(path: &Path) {
+    let backup = path.with_extension("json.bak");
+    match fs::copy(path, &backup) {
+        Ok(_) => info!("Backed up options to {:?}", backup),
+        Err(err) => error!("Could not back up options: {:?}", err),
+    }
+}
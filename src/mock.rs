@@ -0,0 +1,83 @@
+use std::{sync::Mutex, time::{Duration, Instant}};
+use log::info;
+
+use crate::{APIResponse, APIResponseData, APIResponseStateData, ThermostatConfig};
+
+const RESPONSE_DELAY: Duration = Duration::from_millis(50); // Roughly a server on the local network.
+const AMBIENT_TEMP: f32 = 16.0; // What the room cools down to without heating.
+const HEATING_RATE: f32 = 0.01; // In °C per second.
+const COOLING_RATE: f32 = 0.002; // In °C per second and °C above the ambient temperature.
+
+/// In-process stand-in for the thermostat API, so the UI can be run without a server (`--mock`).
+/// Every thermostat starts out the same and heats or cools down depending on its config.
+#[derive(Debug)]
+pub struct MockApi {
+    thermostats: Mutex<Vec<MockThermostat>>,
+    started: Instant,
+}
+
+#[derive(Debug)]
+struct MockThermostat {
+    config: ThermostatConfig,
+    temperature: f32,
+    updated: Instant,
+}
+
+impl MockThermostat {
+    fn is_heating(&self) -> bool {
+        self.config.master_switch && (self.config.force || self.temperature < self.config.target_temp)
+    }
+
+    /// Heats or cools down for the time since the last request.
+    fn advance(&mut self) {
+        let secs = self.updated.elapsed().as_secs_f32();
+        self.updated = Instant::now();
+
+        self.temperature += if self.is_heating() { HEATING_RATE * secs } else { -(self.temperature - AMBIENT_TEMP) * COOLING_RATE * secs };
+    }
+}
+
+impl MockApi {
+    pub fn new(thermostats: usize) -> Self {
+        info!("Using a mock API instead of the thermostats");
+        let thermostat = || MockThermostat {
+            config: ThermostatConfig { master_switch: true, force: false, target_temp: 20.0, co2_target: None },
+            temperature: 18.5,
+            updated: Instant::now(),
+        };
+
+        Self { thermostats: Mutex::new((0..thermostats).map(|_| thermostat()).collect()), started: Instant::now() }
+    }
+
+    pub async fn get(&self, endpoint: usize, include_config: bool) -> APIResponse {
+        tokio::time::sleep(RESPONSE_DELAY).await;
+        self.respond(endpoint, |thermostat| include_config.then_some(thermostat.config))
+    }
+
+    pub async fn patch(&self, endpoint: usize, config: ThermostatConfig) -> APIResponse {
+        tokio::time::sleep(RESPONSE_DELAY).await;
+        self.respond(endpoint, |thermostat| {
+            thermostat.config = config;
+            Some(config)
+        })
+    }
+
+    fn respond(&self, endpoint: usize, f: impl FnOnce(&mut MockThermostat) -> Option<ThermostatConfig>) -> APIResponse {
+        let mut thermostats = self.thermostats.lock().unwrap();
+        let thermostat = &mut thermostats[endpoint];
+        thermostat.advance();
+        let config = f(thermostat);
+
+        // Slow waves, so the readings look alive.
+        let minutes = self.started.elapsed().as_secs_f32() / 60.0;
+        let state = APIResponseStateData {
+            available: true,
+            temperature: (thermostat.temperature * 10.0).round() / 10.0,
+            co2: 700 + (400.0 * (minutes / 7.0).sin()) as i32,
+            is_heating: thermostat.is_heating(),
+            humidity: Some(45.0 + 5.0 * (minutes / 11.0).sin()),
+        };
+
+        APIResponse { success: true, data: Some(APIResponseData { config, state }), error: None, time: Some(chrono::Utc::now().timestamp()) }
+    }
+}
@@ -0,0 +1,90 @@
+use std::cell::RefCell;
+use log::error;
+use notify_rust::Notification;
+use slint::ComponentHandle;
+
+use crate::{AppWindow, Functions, Singletons, Texts};
+
+const TARGET_MARGIN: f32 = 0.5; // °C the temperature has to drop below the target before notifying again.
+
+thread_local! {
+    // Only ever touched from the UI thread.
+    static TARGET: RefCell<TargetTracker> = RefCell::new(TargetTracker::default());
+    static CO2: RefCell<Hysteresis> = RefCell::new(Hysteresis::default());
+}
+
+/// Tracks whether a value is at or above a threshold.
+/// Once above, it has to drop `margin` below the threshold to count as below again,
+/// so a value hovering around the threshold does not flip back and forth.
+#[derive(Debug, Default)]
+struct Hysteresis {
+    above: Option<bool>, // Unknown until the first value arrives, which counts as below.
+}
+
+impl Hysteresis {
+    /// Returns whether the value just rose to or above the threshold.
+    fn update(&mut self, value: f32, threshold: f32, margin: f32) -> bool {
+        let above = match self.above {
+            Some(true) => value > threshold - margin,
+            _ => value >= threshold,
+        };
+
+        let rose = self.above != Some(true) && above;
+        self.above = Some(above);
+        rose
+    }
+}
+
+#[derive(Debug, Default)]
+struct TargetTracker {
+    temp: Hysteresis,
+    was_heating: bool,
+}
+
+/// Notifies the user when heating brought the temperature up to the target, if enabled.
+pub fn check_target_reached(ui: &AppWindow, current_temp: f32, is_heating: bool) {
+    let singletons = ui.global::<Singletons>();
+    let target_temp = singletons.get_config().target_temp;
+
+    let reached = TARGET.with_borrow_mut(|tracker| {
+        let rose = tracker.temp.update(current_temp, target_temp, TARGET_MARGIN);
+        let reached = rose && tracker.was_heating;
+        tracker.was_heating = is_heating;
+        reached
+    });
+
+    if reached && singletons.get_options().notify_on_target {
+        let functions = ui.global::<Functions>();
+        show(ui.global::<Texts>().invoke_target_reached(functions.invoke_format_temp(current_temp), functions.invoke_format_temp(target_temp)).into());
+    }
+}
+
+/// Warns the user when the CO₂ level rises above the alert threshold, if one is set.
+/// The alert clears once the level drops the configured margin below the threshold.
+pub fn check_co2(ui: &AppWindow, co2: i32) {
+    let singletons = ui.global::<Singletons>();
+    let options = singletons.get_options();
+    if options.co2_alert_threshold <= 0 {
+        singletons.set_co2_alert(false);
+        return;
+    }
+
+    let (rose, above) = CO2.with_borrow_mut(|tracker| {
+        let rose = tracker.update(co2 as f32, options.co2_alert_threshold as f32, options.co2_alert_margin as f32);
+        (rose, tracker.above == Some(true))
+    });
+    singletons.set_co2_alert(above);
+
+    if rose {
+        show(ui.global::<Texts>().invoke_co2_high(co2, options.co2_alert_threshold).into());
+    }
+}
+
+/// Shows a desktop notification without blocking the UI thread.
+fn show(body: String) {
+    tokio::task::spawn_blocking(move || {
+        if let Err(err) = Notification::new().summary("Thermostat").body(&body).show() {
+            error!("Could not show notification: {:?}", err);
+        }
+    });
+}
@@ -0,0 +1,78 @@
+use std::{cell::RefCell, collections::HashMap, fs, path::Path};
+use anyhow::Result;
+use chrono::{Local, NaiveDate, Timelike};
+use log::error;
+use slint::ComponentHandle;
+
+use crate::{thermostats, write_json, AppWindow, Singletons};
+
+const MAX_GAP_SECS: i64 = 5 * 60; // Longer between two readings and it is anyone's guess whether it heated in between.
+
+thread_local! {
+    // Only ever touched from the UI thread.
+    static RUNTIME: RefCell<Runtime> = RefCell::new(Runtime::default());
+}
+
+/// How long each thermostat has been heating today, as far as the app has seen.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Default)]
+struct Runtime {
+    date: Option<NaiveDate>, // Day the totals are of.
+    totals: HashMap<usize, i64>, // Seconds of heating by the index of the thermostat in the options.
+    #[serde(skip)]
+    last: HashMap<usize, (i64, bool)>, // Time of and whether heating at the last reading.
+}
+
+impl Runtime {
+    fn record(&mut self, endpoint: usize, now: chrono::DateTime<Local>, heating: bool) {
+        let today = now.date_naive();
+        if self.date != Some(today) {
+            self.date = Some(today);
+            self.totals.clear();
+        }
+
+        let time = now.timestamp();
+        if let Some((last_time, was_heating)) = self.last.insert(endpoint, (time, heating)) {
+            let gap = time - last_time;
+            if was_heating && (0..=MAX_GAP_SECS).contains(&gap) {
+                // Only the part since midnight counts for today.
+                *self.totals.entry(endpoint).or_default() += gap.min(now.num_seconds_from_midnight() as i64);
+            }
+        }
+    }
+
+    fn total(&self, endpoint: usize) -> i64 {
+        if self.date != Some(Local::now().date_naive()) {
+            return 0;
+        }
+
+        self.totals.get(&endpoint).copied().unwrap_or_default()
+    }
+}
+
+/// Loads the totals saved by the last session, if any.
+pub fn load(path: &Path) {
+    let Ok(contents) = fs::read_to_string(path) else { return };
+
+    match serde_json::from_str::<Runtime>(&contents) {
+        Ok(runtime) => RUNTIME.set(runtime),
+        Err(err) => error!("Could not read heating runtime from disk: {:?}", err),
+    }
+}
+
+pub fn save(path: &Path) -> Result<()> {
+    RUNTIME.with_borrow(|runtime| write_json(runtime, path))
+}
+
+/// Counts the time since the last reading of `endpoint` if it was heating then.
+pub fn record(ui: &AppWindow, endpoint: usize, heating: bool) {
+    RUNTIME.with_borrow_mut(|runtime| runtime.record(endpoint, Local::now(), heating));
+    if thermostats::is_selected(endpoint) {
+        update_display(ui);
+    }
+}
+
+/// Shows today's total of the selected thermostat.
+pub fn update_display(ui: &AppWindow) {
+    let total = RUNTIME.with_borrow(|runtime| runtime.total(thermostats::selected()));
+    ui.global::<Singletons>().set_heating_minutes((total / 60) as i32);
+}
@@ -0,0 +1,156 @@
+use chrono::{Datelike, Local, NaiveDateTime, NaiveTime, Timelike, Weekday};
+use log::info;
+use slint::ComponentHandle;
+use std::{cell::RefCell, collections::HashMap, time::Duration};
+
+use crate::{boost, hold, modify_config_automatically, toast, Api, AppWindow, Functions, Singletons, Texts, ThermostatConfig};
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+thread_local! {
+    // Only ever touched from the UI thread.
+    static SCHEDULE: RefCell<Vec<ScheduleEntry>> = const { RefCell::new(Vec::new()) };
+    static WEEKDAY_TARGETS: RefCell<HashMap<Weekday, f32>> = RefCell::new(HashMap::new());
+}
+
+/// A target temperature that becomes active at the same time every week.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+pub struct ScheduleEntry {
+    pub weekday: Weekday,
+    pub time: NaiveTime,
+    pub target_temp: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>, // Shown when the entry sets the target, e.g. "night setback".
+}
+
+impl ScheduleEntry {
+    /// Minutes since Monday 00:00 at which this entry becomes active.
+    fn minute_of_week(&self) -> u32 {
+        minute_of_week(self.weekday, self.time.hour(), self.time.minute())
+    }
+}
+
+fn minute_of_week(weekday: Weekday, hour: u32, minute: u32) -> u32 {
+    weekday.num_days_from_monday() * MINUTES_PER_DAY + hour * 60 + minute
+}
+
+/// Finds the index of the entry that is active at `now`, which is the last one that started before it.
+/// If none started yet this week, the last entry of the previous week is still active.
+fn active_entry(schedule: &[ScheduleEntry], now: NaiveDateTime) -> Option<usize> {
+    let now = minute_of_week(now.weekday(), now.hour(), now.minute());
+    let entries = || schedule.iter().enumerate();
+
+    entries()
+        .filter(|(_, entry)| entry.minute_of_week() <= now)
+        .max_by_key(|(_, entry)| entry.minute_of_week())
+        .or_else(|| entries().max_by_key(|(_, entry)| entry.minute_of_week()))
+        .map(|(i, _)| i)
+}
+
+/// The target temperature the schedule dictates at `now`, if there is a schedule.
+pub fn target_at(now: NaiveDateTime) -> Option<f32> {
+    SCHEDULE.with_borrow(|schedule| active_entry(schedule, now).map(|i| schedule[i].target_temp))
+}
+
+/// The default target temperature for the weekday of `now`, if one is set for it.
+pub fn weekday_target(now: NaiveDateTime) -> Option<f32> {
+    WEEKDAY_TARGETS.with_borrow(|targets| targets.get(&now.weekday()).copied())
+}
+
+/// Sets the default target temperature of the new day whenever a day with one starts.
+/// Like the schedule, the target is left alone on startup and during a temperature hold or boost.
+pub fn start_weekday_defaults(ui: &AppWindow, api: Api, targets: HashMap<Weekday, f32>) {
+    WEEKDAY_TARGETS.set(targets.clone());
+    if targets.is_empty() {
+        return;
+    }
+
+    let ui_handle = ui.as_weak();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        let mut today = Local::now().date_naive();
+
+        loop {
+            interval.tick().await;
+
+            let now = Local::now().date_naive();
+            if now == today {
+                continue;
+            }
+            today = now;
+
+            let Some(&target_temp) = targets.get(&today.weekday()) else { continue };
+            info!("{} started, setting its default target temperature {}", today.weekday(), target_temp);
+
+            let api = api.clone();
+            let weekday = today.weekday();
+            let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+                let texts = ui.global::<Texts>();
+                apply_target(&ui, &api, target_temp, &texts.invoke_weekday_default(), &texts.invoke_weekday(weekday.number_from_monday() as i32));
+            });
+        }
+    });
+}
+
+/// Sets a target the schedule or the weekday defaults dictate, telling the user why it changed.
+/// A hold restores the scheduled target by itself once it ends, and a boost ends at this target instead of the one from before it.
+fn apply_target(ui: &AppWindow, api: &Api, target_temp: f32, cause: &str, reason: &str) {
+    if hold::is_active() {
+        info!("Temperature is held, not applying the scheduled target {} yet", target_temp);
+        return;
+    }
+    if boost::is_active() {
+        info!("Boosting, setting the scheduled target {} once the boost ends", target_temp);
+        boost::set_restore_target(target_temp);
+        return;
+    }
+
+    let before = ui.global::<Singletons>().get_config().target_temp;
+    modify_config_automatically(ui, api, |cfg: &mut ThermostatConfig| {
+        cfg.target_temp = target_temp;
+    });
+
+    let after = ui.global::<Singletons>().get_config().target_temp;
+    if after != before {
+        let target = ui.global::<Functions>().invoke_format_temp(after);
+        toast::show_automatic(ui, cause, &ui.global::<Texts>().invoke_scheduled_target(reason.into(), target));
+    }
+}
+
+/// Periodically checks the schedule and sets the target temperature whenever a new entry becomes active.
+/// Only transitions are applied, so a manual change holds until the next entry starts.
+/// Transitions during a temperature hold or boost are left to those, which restore the scheduled target once they end.
+pub fn start_scheduler(ui: &AppWindow, api: Api, schedule: Vec<ScheduleEntry>) {
+    SCHEDULE.set(schedule.clone());
+    if schedule.is_empty() {
+        return;
+    }
+
+    let ui_handle = ui.as_weak();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        // Whatever is active on startup is left alone, the user may have changed it since it started.
+        let mut current = active_entry(&schedule, Local::now().naive_local());
+
+        loop {
+            interval.tick().await; // Run every minute
+
+            let active = active_entry(&schedule, Local::now().naive_local());
+            if active == current {
+                continue;
+            }
+            current = active;
+
+            let Some(entry) = active.map(|i| schedule[i].clone()) else { continue };
+            info!("Schedule entry {:?} became active", entry);
+
+            let api = api.clone();
+            let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+                let texts = ui.global::<Texts>();
+                let reason = entry.name.clone()
+                    .unwrap_or_else(|| format!("{} {}", texts.invoke_weekday(entry.weekday.number_from_monday() as i32), entry.time.format("%H:%M")));
+                apply_target(&ui, &api, entry.target_temp, &texts.invoke_schedule(), &reason);
+            });
+        }
+    });
+}
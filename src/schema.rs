@@ -0,0 +1,69 @@
+use std::{collections::HashSet, sync::Mutex};
+use log::warn;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+use serde_json::{Map, Value};
+
+use crate::{APIResponseStateData, ThermostatConfig};
+
+/// Schema differences already logged, so a newer server does not flood the log on every poll.
+static LOGGED: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+fn warn_once(msg: String) {
+    if LOGGED.lock().unwrap().get_or_insert_with(HashSet::new).insert(msg.clone()) {
+        warn!("{}, the server may be of a newer version", msg);
+    }
+}
+
+/// The state as a server may send it, with every field optional so one that was dropped or changed does not fail the whole response.
+#[derive(Deserialize)]
+pub struct RawState {
+    available: Option<Value>,
+    temperature: Option<Value>,
+    co2: Option<Value>,
+    is_heating: Option<Value>,
+    humidity: Option<Value>,
+    #[serde(flatten)]
+    unknown: Map<String, Value>,
+}
+
+impl From<RawState> for APIResponseStateData {
+    fn from(raw: RawState) -> Self {
+        if !raw.unknown.is_empty() {
+            warn_once(format!("API state has unknown fields {:?}", raw.unknown.keys().collect::<Vec<_>>()));
+        }
+
+        // Without a reading there is nothing to show, which is what an offline sensor looks like too.
+        let temperature = field::<f32>("temperature", raw.temperature, true);
+        Self {
+            available: temperature.is_some() && field("available", raw.available, true).unwrap_or(true),
+            temperature: temperature.unwrap_or_default(),
+            co2: field("co2", raw.co2, true).unwrap_or_default(),
+            is_heating: field("is_heating", raw.is_heating, true).unwrap_or_default(),
+            humidity: field("humidity", raw.humidity, false), // Not reported by older servers.
+        }
+    }
+}
+
+/// Reads a field of the state, logging once if it is missing while `expected` or of an unexpected type.
+fn field<T: DeserializeOwned>(name: &str, value: Option<Value>, expected: bool) -> Option<T> {
+    let Some(value) = value.filter(|value| !value.is_null()) else {
+        if expected {
+            warn_once(format!("API state has no {}", name));
+        }
+        return None;
+    };
+
+    serde_json::from_value(value)
+        .map_err(|err| warn_once(format!("Could not read {} of the API state: {}", name, err)))
+        .ok()
+}
+
+/// Deserializes the config in a response, treating one that cannot be read as absent.
+pub fn lenient_config<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<ThermostatConfig>, D::Error> {
+    let value = Option::<Value>::deserialize(deserializer)?;
+    Ok(value.and_then(|value| {
+        serde_json::from_value(value)
+            .map_err(|err| warn_once(format!("Could not read the config in an API response: {}", err)))
+            .ok()
+    }))
+}
@@ -0,0 +1,172 @@
+use std::{cell::RefCell, collections::HashMap};
+use log::{info, warn};
+use slint::{ComponentHandle, ModelRc, SharedString, VecModel};
+
+use crate::{AppWindow, KeyEvent, ShortcutAction, ShortcutInfo, Singletons};
+
+thread_local! {
+    // Only ever touched from the UI thread.
+    static KEYMAP: RefCell<HashMap<Shortcut, Vec<Key>>> = RefCell::new(HashMap::new());
+}
+
+/// What a key press on the window does, named like this in the `keymap` of the options.
+/// Everything about a shortcut lives in [`Shortcut::binding`], so the cheat sheet cannot drift from the keys handled.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Shortcut {
+    Help,
+    Hide,
+    TargetUp,
+    TargetDown,
+    ResetTarget,
+    MasterSwitch,
+    Force,
+    RequireCo2,
+    Co2Preset,
+    Boost,
+    Hold,
+    Undo,
+    Refresh,
+    CopyState,
+    History,
+    Compact,
+    OnTop,
+    ClickThrough,
+    Theme,
+    TemperatureUnit,
+    ScaleUp,
+    ScaleDown,
+    EditOptions,
+    Latency,
+}
+
+/// Default keys of a shortcut, written like in the keymap, and what it does.
+struct Binding {
+    keys: &'static [&'static str], // Any of them triggers the shortcut.
+    action: ShortcutAction, // Described on the cheat sheet in the language of the labels.
+}
+
+impl Shortcut {
+    /// In the order shown on the cheat sheet.
+    const ALL: [Shortcut; 24] = [
+        Self::Help, Self::Hide, Self::TargetUp, Self::TargetDown, Self::ResetTarget, Self::MasterSwitch, Self::Force,
+        Self::RequireCo2, Self::Co2Preset, Self::Boost, Self::Hold, Self::Undo, Self::Refresh, Self::CopyState, Self::History, Self::Compact, Self::OnTop,
+        Self::ClickThrough, Self::Theme, Self::TemperatureUnit, Self::ScaleUp, Self::ScaleDown, Self::EditOptions, Self::Latency,
+    ];
+
+    const fn binding(self) -> Binding {
+        const fn key(keys: &'static [&'static str], action: ShortcutAction) -> Binding {
+            Binding { keys, action }
+        }
+
+        match self {
+            Self::Help => key(&["?"], ShortcutAction::Help),
+            Self::Hide => key(&["Esc"], ShortcutAction::Hide),
+            Self::TargetUp => key(&["Up"], ShortcutAction::TargetUp),
+            Self::TargetDown => key(&["Down"], ShortcutAction::TargetDown),
+            Self::ResetTarget => key(&["0", "Home"], ShortcutAction::ResetTarget),
+            Self::MasterSwitch => key(&["m"], ShortcutAction::MasterSwitch),
+            Self::Force => key(&["f"], ShortcutAction::Force),
+            Self::RequireCo2 => key(&["a"], ShortcutAction::RequireCo2),
+            Self::Co2Preset => key(&["n"], ShortcutAction::Co2Preset),
+            Self::Boost => key(&["b"], ShortcutAction::Boost),
+            Self::Hold => key(&["p"], ShortcutAction::Hold),
+            Self::Undo => key(&["Ctrl+Z"], ShortcutAction::Undo),
+            Self::Refresh => key(&["r"], ShortcutAction::Refresh),
+            Self::CopyState => key(&["Ctrl+C"], ShortcutAction::CopyState),
+            Self::History => key(&["h"], ShortcutAction::History),
+            Self::Compact => key(&["c"], ShortcutAction::Compact),
+            Self::OnTop => key(&["o"], ShortcutAction::OnTop),
+            Self::ClickThrough => key(&["g"], ShortcutAction::ClickThrough),
+            Self::Theme => key(&["t"], ShortcutAction::Theme),
+            Self::TemperatureUnit => key(&["u"], ShortcutAction::TemperatureUnit),
+            Self::ScaleUp => key(&["Ctrl+=", "Ctrl++"], ShortcutAction::ScaleUp),
+            Self::ScaleDown => key(&["Ctrl+-"], ShortcutAction::ScaleDown),
+            Self::EditOptions => key(&["e"], ShortcutAction::EditOptions),
+            Self::Latency => key(&["l"], ShortcutAction::Latency),
+        }
+    }
+
+    /// The shortcut a key press triggers, if any.
+    pub fn from_event(e: &KeyEvent) -> Option<Self> {
+        KEYMAP.with_borrow(|keymap| {
+            Self::ALL.into_iter().find(|shortcut| keymap.get(shortcut).is_some_and(|keys| keys.iter().any(|key| key.matches(e))))
+        })
+    }
+}
+
+/// A key as the window receives it.
+#[derive(Debug, Clone)]
+pub struct Key {
+    name: String, // As written in the keymap, also shown on the cheat sheet.
+    text: String, // Text of the key event.
+    control: bool,
+}
+
+impl Key {
+    /// Parses a key like `f`, `?`, `Ctrl+Z`, `Up` or `Home`.
+    pub fn parse(name: &str) -> Option<Self> {
+        let (control, key) = match name.strip_prefix("Ctrl+") {
+            Some(key) => (true, key),
+            None => (false, name),
+        };
+
+        let text = match key.to_ascii_lowercase().as_str() {
+            "esc" | "escape" => "\u{1b}".to_owned(),
+            "up" => "\u{f700}".to_owned(),
+            "down" => "\u{f701}".to_owned(),
+            "left" => "\u{f702}".to_owned(),
+            "right" => "\u{f703}".to_owned(),
+            "home" => "\u{f729}".to_owned(),
+            "end" => "\u{f72b}".to_owned(),
+            "space" => " ".to_owned(),
+            _ if key.chars().count() != 1 => return None,
+            // Letters come in lowercase while Ctrl is held, otherwise a capital means Shift.
+            _ if control => key.to_lowercase(),
+            _ => key.to_owned(),
+        };
+        Some(Self { name: name.to_owned(), text, control })
+    }
+
+    fn matches(&self, e: &KeyEvent) -> bool {
+        self.control == e.modifiers.control && self.text == e.text.as_str()
+    }
+
+    fn same_as(&self, other: &Key) -> bool {
+        self.control == other.control && self.text == other.text
+    }
+}
+
+/// Sets up the keys of all shortcuts, those in `keymap` replacing the defaults, and fills the cheat sheet shown with `?`.
+pub fn init(ui: &AppWindow, keymap: &HashMap<Shortcut, String>) {
+    let keymap: HashMap<Shortcut, Vec<Key>> = Shortcut::ALL.into_iter()
+        .map(|shortcut| {
+            let keys = match keymap.get(&shortcut) {
+                Some(name) => {
+                    info!("Shortcut {:?} is bound to {}", shortcut, name);
+                    Key::parse(name).into_iter().collect()
+                },
+                None => shortcut.binding().keys.iter().filter_map(|name| Key::parse(name)).collect(),
+            };
+            (shortcut, keys)
+        })
+        .collect();
+
+    // A key bound twice only triggers the shortcut listed first.
+    for (i, shortcut) in Shortcut::ALL.iter().enumerate() {
+        for other in &Shortcut::ALL[i + 1..] {
+            if keymap[shortcut].iter().any(|key| keymap[other].iter().any(|other_key| key.same_as(other_key))) {
+                warn!("Shortcuts {:?} and {:?} share a key, it only triggers {:?}", shortcut, other, shortcut);
+            }
+        }
+    }
+
+    let shortcuts: Vec<ShortcutInfo> = Shortcut::ALL.into_iter()
+        .map(|shortcut| {
+            let keys: Vec<&str> = keymap[&shortcut].iter().map(|key| key.name.as_str()).collect();
+            ShortcutInfo { keys: SharedString::from(keys.join(" / ")), action: shortcut.binding().action }
+        })
+        .collect();
+    ui.global::<Singletons>().set_shortcuts(ModelRc::new(VecModel::from(shortcuts)));
+    KEYMAP.set(keymap);
+}
@@ -0,0 +1,33 @@
+use dark_light::Mode;
+use log::{info, warn};
+use slint::ComponentHandle;
+
+use crate::{AppWindow, Singletons};
+
+/// Tells the UI whether the OS prefers a dark theme, for the system theme option, and keeps it up to date.
+pub fn watch_system_theme(ui: &AppWindow) {
+    match dark_light::detect() {
+        Ok(mode) => set_system_mode(ui, mode),
+        Err(err) => warn!("Could not detect the system theme, assuming dark: {}", err),
+    }
+
+    let ui_handle = ui.as_weak();
+    std::thread::spawn(move || {
+        let watcher = match dark_light::subscribe() {
+            Ok(watcher) => watcher,
+            Err(err) => {
+                info!("Cannot watch for system theme changes: {}", err);
+                return;
+            },
+        };
+
+        for mode in watcher.iter() {
+            let _ = ui_handle.upgrade_in_event_loop(move |ui| set_system_mode(&ui, mode));
+        }
+    });
+}
+
+fn set_system_mode(ui: &AppWindow, mode: Mode) {
+    // The widget was designed dark, so stay dark unless the OS explicitly prefers light.
+    ui.global::<Singletons>().set_system_dark(mode != Mode::Light);
+}
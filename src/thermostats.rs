@@ -0,0 +1,153 @@
+use std::{cell::RefCell, collections::HashMap, sync::atomic::{AtomicUsize, Ordering}, time::Instant};
+use slint::{ComponentHandle, ModelRc, SharedString, VecModel};
+
+use crate::{apply_state, clock, fetch_full_state, frost, history, metrics, runtime, trend, Api, APIResponseStateData, AppWindow, Singletons, ThermostatConfig, POLL_INTERVAL_SECS};
+
+/// Index of the thermostat shown in the UI and controlled by it.
+static SELECTED: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // Only ever touched from the UI thread.
+    static STATES: RefCell<HashMap<usize, APIResponseStateData>> = RefCell::new(HashMap::new());
+    static CONFIGS: RefCell<HashMap<usize, ThermostatConfig>> = RefCell::new(HashMap::new()); // Last accepted by the API.
+    static UPDATED: RefCell<HashMap<usize, Instant>> = RefCell::new(HashMap::new()); // When the state was last received.
+    static SMOOTHED_CO2: RefCell<HashMap<usize, f32>> = RefCell::new(HashMap::new()); // Moving average of the CO₂ readings.
+    static LAST_CO2_TARGETS: RefCell<HashMap<usize, i32>> = RefCell::new(HashMap::new()); // Restored when requiring CO₂ again.
+}
+
+/// A thermostat API the app can control.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+pub struct ThermostatEndpoint {
+    pub name: String,
+    pub api_url: String,
+}
+
+pub fn selected() -> usize {
+    SELECTED.load(Ordering::SeqCst)
+}
+
+pub fn is_selected(endpoint: usize) -> bool {
+    selected() == endpoint
+}
+
+/// Shows the available thermostats in the UI and selects the one from the last session.
+pub fn init(ui: &AppWindow, api: &Api, selected: usize, last_co2_targets: HashMap<usize, i32>) {
+    SELECTED.store(selected, Ordering::SeqCst);
+    LAST_CO2_TARGETS.set(last_co2_targets);
+
+    let names: Vec<SharedString> = api.endpoints.iter().map(|endpoint| endpoint.name.as_str().into()).collect();
+    let singletons = ui.global::<Singletons>();
+    singletons.set_thermostat_names(ModelRc::new(VecModel::from(names)));
+    singletons.set_selected_thermostat(selected as i32);
+    singletons.set_last_co2_target(last_co2_target(ui, selected));
+}
+
+/// CO₂ target in ppm the user last required for a thermostat, `default_co2_target` if none so far.
+pub fn last_co2_target(ui: &AppWindow, endpoint: usize) -> i32 {
+    LAST_CO2_TARGETS.with_borrow(|targets| targets.get(&endpoint).copied())
+        .unwrap_or_else(|| ui.global::<Singletons>().get_options().default_co2_target)
+}
+
+/// Remembers a CO₂ target the user set, to restore when they require CO₂ again after turning it off.
+pub fn remember_co2_target(ui: &AppWindow, endpoint: usize, co2_target: i32) {
+    LAST_CO2_TARGETS.with_borrow_mut(|targets| targets.insert(endpoint, co2_target));
+    if is_selected(endpoint) {
+        ui.global::<Singletons>().set_last_co2_target(co2_target);
+    }
+}
+
+pub fn last_co2_targets() -> HashMap<usize, i32> {
+    LAST_CO2_TARGETS.with_borrow(Clone::clone)
+}
+
+/// Remembers the latest state of a thermostat and shows it if it is the selected one.
+pub fn apply_endpoint_state(ui: &AppWindow, endpoint: usize, state: APIResponseStateData) {
+    STATES.with_borrow_mut(|states| states.insert(endpoint, state.clone()));
+    STATES.with_borrow(|states| metrics::write(ui, states));
+    UPDATED.with_borrow_mut(|updated| updated.insert(endpoint, Instant::now()));
+    runtime::record(ui, endpoint, state.is_heating);
+    clock::check(ui, endpoint);
+    if state.available {
+        smooth_co2(ui, endpoint, state.co2);
+        trend::record(ui, endpoint, state.temperature);
+    }
+
+    if is_selected(endpoint) {
+        apply_state(ui, with_smoothed_co2(endpoint, state));
+    }
+}
+
+/// Folds a CO₂ reading into the moving average by `co2_smoothing`, so sensor noise does not make the colors and alerts flicker.
+fn smooth_co2(ui: &AppWindow, endpoint: usize, co2: i32) {
+    let smoothing = ui.global::<Singletons>().get_options().co2_smoothing;
+    SMOOTHED_CO2.with_borrow_mut(|smoothed| {
+        let average = smoothed.entry(endpoint).or_insert(co2 as f32); // The first reading has nothing to average with.
+        *average = smoothing * *average + (1.0 - smoothing) * co2 as f32;
+    });
+}
+
+/// The state to show, with the smoothed instead of the raw CO₂ reading. The raw one stays in [`STATES`].
+fn with_smoothed_co2(endpoint: usize, state: APIResponseStateData) -> APIResponseStateData {
+    match SMOOTHED_CO2.with_borrow(|smoothed| smoothed.get(&endpoint).copied()) {
+        Some(co2) => APIResponseStateData { co2: co2.round() as i32, ..state },
+        None => state,
+    }
+}
+
+/// Remembers a config the API accepted, to fall back to when a later update fails
+/// and to show instead of the requested one unless `optimistic_updates` is on.
+pub fn confirm_config(ui: &AppWindow, endpoint: usize, cfg: ThermostatConfig) {
+    CONFIGS.with_borrow_mut(|configs| configs.insert(endpoint, cfg));
+    if is_selected(endpoint) {
+        ui.global::<Singletons>().set_confirmed_config(cfg.into());
+    }
+}
+
+pub fn confirmed_config(endpoint: usize) -> Option<ThermostatConfig> {
+    CONFIGS.with_borrow(|configs| configs.get(&endpoint).copied())
+}
+
+/// Switches the UI over to another thermostat.
+/// Its last known config and state are shown right away and refreshed once the API responds.
+pub fn select(ui: &AppWindow, api: &Api, endpoint: usize) {
+    if is_selected(endpoint) || endpoint >= api.endpoints.len() {
+        return;
+    }
+    SELECTED.store(endpoint, Ordering::SeqCst);
+
+    let singletons = ui.global::<Singletons>();
+    singletons.set_selected_thermostat(endpoint as i32);
+    singletons.set_last_co2_target(last_co2_target(ui, endpoint));
+    singletons.set_stale(true);
+    singletons.set_unreachable_url(Default::default()); // Was about the previous thermostat.
+    if let Some(cfg) = confirmed_config(endpoint) {
+        singletons.set_config(cfg.into());
+    }
+    singletons.set_confirmed_config(singletons.get_config()); // Until the API responds, nothing is pending.
+    if let Some(state) = STATES.with_borrow(|states| states.get(&endpoint).cloned()) {
+        singletons.set_state(with_smoothed_co2(endpoint, state).into());
+    }
+
+    history::clear(ui); // The readings so far are of the previous thermostat.
+    runtime::update_display(ui);
+    update_freshness(ui);
+    frost::update_display(ui);
+    trend::update_display(ui);
+    clock::update_display(ui);
+    fetch_full_state(ui, api.clone(), endpoint);
+}
+
+/// Shows how long ago the state of the selected thermostat was received,
+/// flagging it once more than two poll intervals passed without an update.
+pub fn update_freshness(ui: &AppWindow) {
+    let singletons = ui.global::<Singletons>();
+    let Some(updated) = UPDATED.with_borrow(|updated| updated.get(&selected()).copied()) else {
+        singletons.set_updated_secs_ago(-1);
+        singletons.set_updates_stopped(false);
+        return;
+    };
+
+    let secs = updated.elapsed().as_secs();
+    singletons.set_updated_secs_ago(secs.try_into().unwrap_or(i32::MAX));
+    singletons.set_updates_stopped(secs > 2 * POLL_INTERVAL_SECS.load(Ordering::SeqCst));
+}
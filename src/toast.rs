@@ -0,0 +1,72 @@
+use std::time::Duration;
+use slint::{ComponentHandle, SharedString, Timer, TimerMode, Weak};
+
+use crate::{thermostats, AppWindow, Singletons, Texts, ToastKind};
+
+const DISMISS_AFTER: Duration = Duration::from_secs(5);
+const INFO_DISMISS_AFTER: Duration = Duration::from_secs(2); // Confirmations only need a glance.
+const AUTOMATIC_DISMISS_AFTER: Duration = Duration::from_secs(4); // Nobody was looking for it, so a bit longer.
+
+thread_local! {
+    // Slint timers only run on the UI thread.
+    static DISMISS_TIMER: Timer = Timer::default();
+}
+
+/// Briefly shows an error in the UI if it concerns the selected thermostat, can be called from any thread.
+/// The message is put into words on the UI thread, where the translations are. It stays available on the connection indicator until the next success.
+pub fn show_error(ui_handle: Weak<AppWindow>, endpoint: usize, message: impl FnOnce(&Texts) -> SharedString + Send + 'static) {
+    if !thermostats::is_selected(endpoint) {
+        return;
+    }
+
+    let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+        let message = message(&ui.global::<Texts>());
+        ui.global::<Singletons>().set_last_error(message.clone());
+        show(&ui, &message, ToastKind::Error);
+    });
+}
+
+/// Briefly shows feedback on something the user did.
+pub fn show_info(ui: &AppWindow, message: &str) {
+    show(ui, message, ToastKind::Info);
+}
+
+/// Briefly shows that something did not go quite as the user asked.
+pub fn show_warning(ui: &AppWindow, message: &str) {
+    show(ui, message, ToastKind::Warning);
+}
+
+/// Briefly shows why the config just changed by itself, so it is not mistaken for a glitch, e.g. "Schedule: night setback".
+pub fn show_automatic(ui: &AppWindow, cause: &str, message: &str) {
+    show(ui, &format!("{}: {}", cause, message), ToastKind::Automatic);
+}
+
+/// A newer toast replaces the shown one and restarts the dismiss timer.
+fn show(ui: &AppWindow, message: &str, kind: ToastKind) {
+    let singletons = ui.global::<Singletons>();
+    singletons.set_toast_message(message.into());
+    singletons.set_toast_kind(kind);
+
+    let dismiss_after = match kind {
+        ToastKind::Info => INFO_DISMISS_AFTER,
+        ToastKind::Automatic => AUTOMATIC_DISMISS_AFTER,
+        _ => DISMISS_AFTER,
+    };
+    let ui_handle = ui.as_weak();
+    DISMISS_TIMER.with(|timer| timer.start(TimerMode::SingleShot, dismiss_after, move || {
+        if let Some(ui) = ui_handle.upgrade() {
+            ui.global::<Singletons>().set_toast_message(Default::default());
+        }
+    }));
+}
+
+/// Hides the error, because a request succeeded since. Other toasts stay until they are dismissed.
+pub fn clear(ui: &AppWindow) {
+    let singletons = ui.global::<Singletons>();
+    singletons.set_last_error(Default::default());
+
+    if singletons.get_toast_kind() == ToastKind::Error {
+        DISMISS_TIMER.with(Timer::stop);
+        singletons.set_toast_message(Default::default());
+    }
+}
@@ -0,0 +1,92 @@
+use std::{cell::RefCell, io::Cursor};
+use anyhow::Result;
+use slint::{winit_030::WinitWindowAccessor, ComponentHandle};
+use tray_icon::{menu::{Menu, MenuEvent, MenuItem}, Icon, MouseButton, MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent};
+
+use crate::{apply_click_through, set_window_shown, toggle_master_switch, Api, AppWindow, Singletons, Texts};
+
+const ICON_PNG: &[u8] = include_bytes!("../assets/icon.png");
+
+thread_local! {
+    // The tray icon has to stay on the thread that created it, which is the UI thread.
+    static TRAY: RefCell<Option<TrayIcon>> = const { RefCell::new(None) };
+}
+
+/// Adds the tray icon, which keeps the app reachable while the window is hidden.
+pub fn create_tray(ui: &AppWindow, api: Api) -> Result<()> {
+    let texts = ui.global::<Texts>();
+    let show = MenuItem::new(texts.invoke_tray_show(), true, None);
+    let toggle_master = MenuItem::new(texts.invoke_tray_toggle_master_switch(), true, None);
+    let click_through = MenuItem::new(texts.invoke_tray_toggle_click_through(), true, None); // Clicks cannot get the window back once it ignores them.
+    let quit = MenuItem::new(texts.invoke_tray_quit(), true, None);
+    let menu = Menu::with_items(&[&show, &toggle_master, &click_through, &quit])?;
+
+    let tray = TrayIconBuilder::new()
+        .with_menu(Box::new(menu))
+        .with_icon(load_icon()?)
+        .with_tooltip("Thermostat")
+        .build()?;
+    TRAY.with_borrow_mut(|t| *t = Some(tray));
+    update_tooltip(ui);
+
+    let ui_handle = ui.as_weak();
+    let (show, toggle_master, click_through, quit) = (show.id().clone(), toggle_master.id().clone(), click_through.id().clone(), quit.id().clone());
+    MenuEvent::set_event_handler(Some(move |event: MenuEvent| {
+        let api = api.clone();
+        let (show, toggle_master, click_through, quit) = (show.clone(), toggle_master.clone(), click_through.clone(), quit.clone());
+
+        let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+            if event.id == show {
+                show_window(&ui);
+            } else if event.id == toggle_master {
+                toggle_master_switch(&ui, &api);
+                if ui.global::<Singletons>().get_confirming_master_off() {
+                    show_window(&ui); // The confirmation is asked in the window.
+                }
+            } else if event.id == click_through {
+                let singletons = ui.global::<Singletons>();
+                let mut options = singletons.get_options();
+                options.click_through = !options.click_through;
+                singletons.set_options(options);
+                apply_click_through(&ui);
+            } else if event.id == quit {
+                let _ = slint::quit_event_loop(); // Only fails if the event loop is already gone.
+            }
+        });
+    }));
+
+    let ui_handle = ui.as_weak();
+    TrayIconEvent::set_event_handler(Some(move |event: TrayIconEvent| {
+        if let TrayIconEvent::Click { button: MouseButton::Left, button_state: MouseButtonState::Up, .. } = event {
+            let _ = ui_handle.upgrade_in_event_loop(|ui| show_window(&ui));
+        }
+    }));
+
+    Ok(())
+}
+
+/// Shows the current temperature in the tooltip of the tray icon.
+pub fn update_tooltip(ui: &AppWindow) {
+    let tooltip = ui.global::<Texts>().invoke_tray_tooltip();
+
+    TRAY.with_borrow(|tray| {
+        if let Some(tray) = tray {
+            let _ = tray.set_tooltip(Some(tooltip)); // A stale tooltip is not worth reporting.
+        }
+    });
+}
+
+pub fn show_window(ui: &AppWindow) {
+    let _ = ui.show(); // Showing an already visible window is a no-op.
+    ui.window().with_winit_window(|window| window.focus_window());
+    set_window_shown(true);
+}
+
+fn load_icon() -> Result<Icon> {
+    let mut reader = png::Decoder::new(Cursor::new(ICON_PNG)).read_info()?;
+    let mut rgba = vec![0; reader.output_buffer_size().unwrap_or_default()];
+    let info = reader.next_frame(&mut rgba)?;
+    rgba.truncate(info.buffer_size());
+
+    Ok(Icon::from_rgba(rgba, info.width, info.height)?)
+}
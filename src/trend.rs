@@ -0,0 +1,59 @@
+use std::{cell::RefCell, collections::{HashMap, VecDeque}, time::{Duration, Instant}};
+use slint::ComponentHandle;
+
+use crate::{thermostats, AppWindow, Singletons, Trend};
+
+const WINDOW: Duration = Duration::from_secs(10 * 60); // Readings older than this do not count toward the trend.
+const MIN_SPAN: Duration = Duration::from_secs(2 * 60); // Anything shorter is too little to tell.
+const MIN_READINGS: usize = 3;
+const DEADBAND: f32 = 0.3; // In °C per hour, slower changes are sensor noise as far as the trend is concerned.
+
+thread_local! {
+    // Only ever touched from the UI thread.
+    static READINGS: RefCell<HashMap<usize, VecDeque<(Instant, f32)>>> = RefCell::new(HashMap::new());
+}
+
+/// Adds a temperature reading of a thermostat and shows the trend if it is the selected one.
+pub fn record(ui: &AppWindow, endpoint: usize, temp: f32) {
+    let now = Instant::now();
+    READINGS.with_borrow_mut(|readings| {
+        let readings = readings.entry(endpoint).or_default();
+        readings.push_back((now, temp));
+        while readings.front().is_some_and(|(time, _)| now - *time > WINDOW) {
+            readings.pop_front();
+        }
+    });
+
+    if thermostats::is_selected(endpoint) {
+        update_display(ui);
+    }
+}
+
+/// Shows whether the temperature of the selected thermostat is rising, falling or about steady.
+pub fn update_display(ui: &AppWindow) {
+    let per_hour = READINGS.with_borrow(|readings| readings.get(&thermostats::selected()).and_then(slope));
+    let trend = match per_hour {
+        Some(per_hour) if per_hour > DEADBAND => Trend::Rising,
+        Some(per_hour) if per_hour < -DEADBAND => Trend::Falling,
+        _ => Trend::Flat,
+    };
+    ui.global::<Singletons>().set_temp_trend(trend);
+}
+
+/// Least-squares slope of the readings in °C per hour, if there are enough of them.
+fn slope(readings: &VecDeque<(Instant, f32)>) -> Option<f32> {
+    let (first, _) = *readings.front()?;
+    let (last, _) = *readings.back()?;
+    if readings.len() < MIN_READINGS || last - first < MIN_SPAN {
+        return None;
+    }
+
+    let hours = |time: Instant| (time - first).as_secs_f32() / 3600.0;
+    let n = readings.len() as f32;
+    let mean_x = readings.iter().map(|&(time, _)| hours(time)).sum::<f32>() / n;
+    let mean_y = readings.iter().map(|&(_, temp)| temp).sum::<f32>() / n;
+
+    let covariance: f32 = readings.iter().map(|&(time, temp)| (hours(time) - mean_x) * (temp - mean_y)).sum();
+    let variance: f32 = readings.iter().map(|&(time, _)| (hours(time) - mean_x).powi(2)).sum();
+    Some(covariance / variance) // Not zero, the readings span MIN_SPAN.
+}
@@ -0,0 +1,71 @@
+use std::{cell::RefCell, collections::VecDeque, time::{Duration, Instant}};
+use log::info;
+use slint::ComponentHandle;
+
+use crate::{restore_config, thermostats, toast, Api, AppWindow, Singletons, Texts, ThermostatConfig};
+
+const DEPTH: usize = 10; // Number of changes that can be undone.
+const EXPIRY: Duration = Duration::from_secs(5 * 60); // Changes older than this are not undone anymore, they were no accident.
+
+thread_local! {
+    // Only ever touched from the UI thread.
+    static HISTORY: RefCell<History> = RefCell::new(History::default());
+}
+
+/// Configs from before the most recent changes of the selected thermostat, newest last.
+#[derive(Default)]
+struct History {
+    endpoint: usize,
+    previous: VecDeque<ThermostatConfig>,
+    last: Option<Instant>, // When the last change was recorded.
+}
+
+impl History {
+    /// Forgets changes of another thermostat and ones from too long ago.
+    fn expire(&mut self, endpoint: usize) {
+        if self.endpoint != endpoint || self.last.is_some_and(|last| last.elapsed() > EXPIRY) {
+            self.previous.clear();
+            self.endpoint = endpoint;
+        }
+    }
+}
+
+/// Remembers what the thermostat last accepted before the user changed its config to `changed`, so the change can be undone.
+/// Changes made before the thermostat accepted the previous one, like a burst of key presses, all go back to the same config,
+/// so that is only remembered once and undone in one go.
+pub fn record(changed: ThermostatConfig) {
+    let endpoint = thermostats::selected();
+    let Some(previous) = thermostats::confirmed_config(endpoint).filter(|previous| *previous != changed) else { return };
+
+    HISTORY.with_borrow_mut(|history| {
+        history.expire(endpoint);
+        if history.previous.back() != Some(&previous) {
+            history.previous.push_back(previous);
+        }
+        while history.previous.len() > DEPTH {
+            history.previous.pop_front();
+        }
+        history.last = Some(Instant::now());
+    });
+}
+
+/// Reverts the selected thermostat to the config from before the last change.
+pub fn undo(ui: &AppWindow, api: &Api) {
+    let singletons = ui.global::<Singletons>();
+    if singletons.get_options().read_only {
+        return;
+    }
+
+    let previous = HISTORY.with_borrow_mut(|history| {
+        history.expire(thermostats::selected());
+        history.previous.pop_back()
+    });
+    let Some(previous) = previous else {
+        toast::show_info(ui, &ui.global::<Texts>().invoke_nothing_to_undo());
+        return;
+    };
+
+    info!("Undoing the last change, back to {:?}", previous);
+    restore_config(ui, api, previous);
+    toast::show_info(ui, &ui.global::<Texts>().invoke_undid_last_change());
+}